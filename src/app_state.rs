@@ -2,4 +2,12 @@
 pub enum AppState {
     Menu,
     InGame,
-}
\ No newline at end of file
+}
+/// Which level the game plays when it's entered from the menu.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum GameMode {
+    #[default]
+    Normal,
+    /// A level generated from today's date, the same for every player.
+    Daily,
+}
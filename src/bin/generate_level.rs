@@ -0,0 +1,31 @@
+//! Generates a solvable level and prints it as RON, ready to drop into `assets/levels`.
+//!
+//! Usage: `generate_level <seed> [output file]`
+
+use std::{env, fs};
+
+use anyhow::Context;
+use bevy_unfair_advantage::in_game::generator::{self, GeneratorOptions};
+
+fn main() -> anyhow::Result<()> {
+    let mut args = env::args().skip(1);
+
+    let seed: u64 = args
+        .next()
+        .context("Usage: generate_level <seed> [output file]")?
+        .parse()
+        .context("The seed must be a whole number")?;
+
+    let (level, solution) = generator::generate(seed, &GeneratorOptions::default())
+        .context("Couldn't find a solvable level for this seed")?;
+
+    eprintln!("Shortest solution is {} moves", solution.len());
+
+    let ron = level.to_ron()?;
+    match args.next() {
+        Some(path) => fs::write(&path, ron).with_context(|| format!("Couldn't write {}", path))?,
+        None => println!("{}", ron),
+    }
+
+    Ok(())
+}
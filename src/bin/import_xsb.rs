@@ -0,0 +1,47 @@
+//! Converts the Sokoban levels in an XSB file into RON levels, one file per level.
+//!
+//! Usage: `import_xsb <levels.xsb> <output directory> [--colours N] [--pushes N]`
+
+use std::{env, fs, path::Path};
+
+use anyhow::{bail, Context};
+use bevy_unfair_advantage::in_game::xsb::{self, XsbOptions};
+
+const USAGE: &str = "Usage: import_xsb <levels.xsb> <output directory> [--colours N] [--pushes N]";
+
+fn main() -> anyhow::Result<()> {
+    let mut args = env::args().skip(1);
+    let input = args.next().context(USAGE)?;
+    let output = args.next().context(USAGE)?;
+
+    let mut options = XsbOptions::default();
+    while let Some(flag) = args.next() {
+        let value = args.next().context(USAGE)?;
+        match flag.as_str() {
+            "--colours" => options.colours = value.parse().context("--colours must be a number")?,
+            "--pushes" => {
+                options.pushes_left = value.parse().context("--pushes must be a number")?
+            }
+            _ => bail!(USAGE),
+        }
+    }
+
+    let xsb = fs::read_to_string(&input).with_context(|| format!("Couldn't read {}", input))?;
+    let levels = xsb::parse_xsb(&xsb, &options)?;
+
+    let stem = Path::new(&input)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("level");
+    fs::create_dir_all(&output)?;
+
+    for (number, level) in levels.iter().enumerate() {
+        let path = Path::new(&output).join(format!("{}_{}.ron", stem, number + 1));
+        fs::write(&path, level.to_ron()?)
+            .with_context(|| format!("Couldn't write {}", path.display()))?;
+    }
+
+    eprintln!("Imported {} levels", levels.len());
+
+    Ok(())
+}
@@ -0,0 +1,32 @@
+//! Renders RON levels as ASCII grids, or turns ASCII grids back into RON levels.
+//!
+//! Usage: `level_ascii <level.ron | level.txt>...`
+
+use std::{env, fs, path::Path};
+
+use anyhow::{bail, Context};
+use bevy_unfair_advantage::in_game::{ascii, LevelData};
+
+fn main() -> anyhow::Result<()> {
+    let paths: Vec<String> = env::args().skip(1).collect();
+    if paths.is_empty() {
+        bail!("Usage: level_ascii <level.ron | level.txt>...");
+    }
+
+    for path in paths {
+        let contents =
+            fs::read_to_string(&path).with_context(|| format!("Couldn't read {}", path))?;
+
+        let output = match Path::new(&path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("ron") => ascii::render_level(&LevelData::from_ron(&contents)?)?,
+            _ => ascii::parse(&contents)?.to_ron()?,
+        };
+
+        println!("{}", output);
+    }
+
+    Ok(())
+}
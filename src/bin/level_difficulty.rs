@@ -0,0 +1,71 @@
+//! Prints a table of difficulty estimates for level files, for ordering a level pack.
+//!
+//! Usage: `level_difficulty <level.ron>...`
+
+use std::{env, fs};
+
+use anyhow::Context;
+use bevy_unfair_advantage::in_game::{metrics, solver::Puzzle, LevelData};
+
+/// How many states to search per level before giving up.
+const MAX_STATES: usize = 1_000_000;
+
+fn main() -> anyhow::Result<()> {
+    let paths: Vec<String> = env::args().skip(1).collect();
+    if paths.is_empty() {
+        anyhow::bail!("Usage: level_difficulty <level.ron>...");
+    }
+
+    println!(
+        "{:<32} {:>6} {:>6} {:>9} {:>9} {:>9}  budgets (used / left)",
+        "level", "moves", "pushes", "branching", "states", "dead"
+    );
+
+    for path in paths {
+        let level = fs::read_to_string(&path)
+            .with_context(|| format!("Couldn't read {}", path))
+            .and_then(|level| LevelData::from_ron(&level))
+            .with_context(|| format!("Couldn't load {}", path))?;
+
+        let puzzle = match Puzzle::from_level(&level) {
+            Ok(puzzle) => puzzle,
+            Err(error) => {
+                println!("{:<32} {}", path, error);
+                continue;
+            }
+        };
+
+        let difficulty = match metrics::analyse(&puzzle, MAX_STATES) {
+            Some(difficulty) => difficulty,
+            None => {
+                println!("{:<32} no solution found", path);
+                continue;
+            }
+        };
+
+        // A search that gave up only gives a lower bound on states, and can't count dead ones
+        let bound = if difficulty.fully_explored { "" } else { "+" };
+        let dead_states = match difficulty.dead_states {
+            Some(dead_states) => dead_states.to_string(),
+            None => "?".to_string(),
+        };
+        let budgets: Vec<String> = difficulty
+            .budgets
+            .iter()
+            .map(|budget| format!("{} {}/{}", budget.kind, budget.pushes_used, budget.pushes_left))
+            .collect();
+
+        println!(
+            "{:<32} {:>6} {:>6} {:>9.2} {:>9} {:>9}  {}",
+            path,
+            difficulty.solution_moves,
+            difficulty.solution_pushes,
+            difficulty.branching_factor,
+            format!("{}{}", difficulty.reachable_states, bound),
+            dead_states,
+            budgets.join(", ")
+        );
+    }
+
+    Ok(())
+}
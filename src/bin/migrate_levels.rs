@@ -0,0 +1,59 @@
+//! Rewrites every level file under a directory in the latest level format.
+//!
+//! Usage: `migrate_levels [directory]`, defaulting to `assets/levels`
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use bevy_unfair_advantage::in_game::{migration, LevelData};
+
+fn level_files(directory: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    for entry in
+        fs::read_dir(directory).with_context(|| format!("Couldn't read {}", directory.display()))?
+    {
+        let path = entry?.path();
+        if path.is_dir() {
+            level_files(&path, files)?;
+        } else if matches!(path.extension(), Some(extension) if extension == "ron") {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let directory = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "assets/levels".to_string());
+
+    let mut files = Vec::new();
+    level_files(Path::new(&directory), &mut files)?;
+    files.sort();
+
+    for path in files {
+        let ron = fs::read_to_string(&path)?;
+        let version = migration::version(&ron)
+            .with_context(|| format!("Couldn't read the version of {}", path.display()))?;
+
+        if version == migration::LATEST_VERSION {
+            continue;
+        }
+
+        let level = LevelData::from_ron(&ron)
+            .with_context(|| format!("Couldn't upgrade {}", path.display()))?;
+        fs::write(&path, level.to_ron()?)?;
+
+        println!(
+            "{}: version {} -> {}",
+            path.display(),
+            version,
+            migration::LATEST_VERSION
+        );
+    }
+
+    Ok(())
+}
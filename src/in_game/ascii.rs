@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Context};
+use bevy::prelude::*;
+
+use crate::app_state::AppState;
+
+use super::Direction;
+use super::GridObject;
+use super::GridPosition;
+use super::LevelData;
+
+/// Cells with nothing in them.
+const EMPTY: char = '.';
+
+/// Keys handed out to legend entries in order, skipping the characters used as glyphs.
+const LEGEND_KEYS: &str = "abcdefghijklmnopqrstuwxyzABCDEFGHIJKLMNPQRSTUVWYZ0123456789";
+
+/// Logs the level as it currently stands when F2 is pressed, ready to paste into a bug report.
+pub struct AsciiPlugin;
+impl Plugin for AsciiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_update(AppState::InGame).with_system(log_state));
+    }
+}
+
+fn log_state(
+    keyboard_input: Res<Input<KeyCode>>,
+    grid_objects: Query<(&GridObject, &GridPosition)>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F2) {
+        return;
+    }
+
+    let objects: Vec<(&GridObject, &GridPosition)> = grid_objects.iter().collect();
+    match render(&objects) {
+        Ok(ascii) => println!("{}", ascii),
+        Err(error) => eprintln!("Couldn't render the level: {}", error),
+    }
+}
+
+/// The glyph for a cell holding only `object`, when it needs no legend entry.
+fn glyph(object: &GridObject) -> Option<char> {
+    match object {
+        GridObject::Player => Some('@'),
+        GridObject::Wall => Some('#'),
+        GridObject::Pit => Some('O'),
+        GridObject::Exit => Some('X'),
+        GridObject::Gem { collected: false } => Some('*'),
+        GridObject::Conveyor(Direction::Up) => Some('^'),
+        GridObject::Conveyor(Direction::Down) => Some('v'),
+        GridObject::Conveyor(Direction::Left) => Some('<'),
+        GridObject::Conveyor(Direction::Right) => Some('>'),
+        _ => None,
+    }
+}
+
+fn from_glyph(glyph: char) -> Option<GridObject> {
+    Some(match glyph {
+        '@' => GridObject::Player,
+        '#' => GridObject::Wall,
+        'O' => GridObject::Pit,
+        'X' => GridObject::Exit,
+        '*' => GridObject::Gem { collected: false },
+        '^' => GridObject::Conveyor(Direction::Up),
+        'v' => GridObject::Conveyor(Direction::Down),
+        '<' => GridObject::Conveyor(Direction::Left),
+        '>' => GridObject::Conveyor(Direction::Right),
+        _ => return None,
+    })
+}
+
+/// State that only exists whilst the level is played, written as a comment in the legend.
+fn runtime_notes(object: &GridObject) -> Vec<String> {
+    let mut notes = Vec::new();
+
+    match object {
+        GridObject::PushBlock {
+            times_pushed,
+            discovered,
+            ..
+        } => {
+            if *times_pushed > 0 {
+                notes.push(format!("pushed {} times", times_pushed));
+            }
+            if *discovered {
+                notes.push("discovered".to_string());
+            }
+        }
+        GridObject::Button(_, Some(_)) => notes.push("pressed".to_string()),
+        GridObject::PressurePlate {
+            pressed: true,
+            release_moves_left,
+            ..
+        } => notes.push(format!(
+            "pressed, {} moves until released",
+            release_moves_left
+        )),
+        GridObject::Gem { collected: true } => notes.push("collected".to_string()),
+        GridObject::Enemy { step, .. } if *step > 0 => notes.push(format!("on step {}", step)),
+        _ => {}
+    }
+
+    notes
+}
+
+/// A grid of the objects, one character per cell, followed by a legend giving every object in
+/// the cells that need one as RON. Cells share a legend entry when they hold the same things.
+pub fn render(objects: &[(&GridObject, &GridPosition)]) -> anyhow::Result<String> {
+    let mut cells: HashMap<GridPosition, Vec<&GridObject>> = HashMap::new();
+    for (object, position) in objects {
+        cells.entry(**position).or_default().push(object);
+    }
+
+    let positions = || cells.keys();
+    let (min_x, max_x, min_y, max_y) = match (
+        positions().map(|position| position.x).min(),
+        positions().map(|position| position.x).max(),
+        positions().map(|position| position.y).min(),
+        positions().map(|position| position.y).max(),
+    ) {
+        (Some(min_x), Some(max_x), Some(min_y), Some(max_y)) => (min_x, max_x, min_y, max_y),
+        _ => bail!("There's nothing to render"),
+    };
+
+    let mut rows = Vec::new();
+    let mut legend: Vec<(char, String)> = Vec::new();
+    let mut keys = LEGEND_KEYS.chars();
+
+    for y in (min_y..=max_y).rev() {
+        let mut row = String::new();
+
+        for x in min_x..=max_x {
+            let cell = match cells.get(&GridPosition { x, y }) {
+                Some(cell) => cell,
+                None => {
+                    row.push(EMPTY);
+                    continue;
+                }
+            };
+
+            if let [object] = cell.as_slice() {
+                if let Some(glyph) = glyph(object) {
+                    row.push(glyph);
+                    continue;
+                }
+            }
+
+            let mut entry = ron::to_string(cell)?;
+
+            let notes: Vec<String> = cell
+                .iter()
+                .flat_map(|object| runtime_notes(object))
+                .collect();
+            if !notes.is_empty() {
+                entry = format!("{} // {}", entry, notes.join(", "));
+            }
+
+            let key = match legend.iter().find(|(_, existing)| *existing == entry) {
+                Some((key, _)) => *key,
+                None => {
+                    let key = keys
+                        .next()
+                        .context("Too many different cells to give each a legend key")?;
+                    legend.push((key, entry));
+                    key
+                }
+            };
+            row.push(key);
+        }
+
+        rows.push(row);
+    }
+
+    let mut ascii = format!("origin: (x: {}, y: {})\n\n", min_x, max_y);
+    ascii.push_str(&rows.join("\n"));
+    if !legend.is_empty() {
+        ascii.push_str("\n\n");
+        for (key, entry) in legend {
+            ascii.push_str(&format!("{}: {}\n", key, entry));
+        }
+    }
+
+    Ok(ascii)
+}
+
+/// The whole level, with its info, rules, palette, goals and rooms ahead of the grid.
+pub fn render_level(level: &LevelData) -> anyhow::Result<String> {
+    let objects: Vec<(&GridObject, &GridPosition)> = level
+        .objects
+        .iter()
+        .map(|(object, position)| (object, position))
+        .collect();
+
+    Ok(format!(
+        "info: {}\nrules: {}\npalette: {}\ngoals: {}\nrooms: {}\n{}",
+        ron::to_string(&level.info)?,
+        ron::to_string(&level.rules)?,
+        ron::to_string(&level.palette)?,
+        ron::to_string(&level.goals)?,
+        ron::to_string(&level.rooms)?,
+        render(&objects)?
+    ))
+}
+
+/// Reads a level back from [`render_level`] or [`render`]. Anything left out of the header
+/// takes its default, and a missing origin puts the top left cell at `(0, 0)`.
+pub fn parse(ascii: &str) -> anyhow::Result<LevelData> {
+    let mut level = LevelData::from_ron("(objects: [])")?;
+    let mut origin = GridPosition { x: 0, y: 0 };
+    let mut rows = Vec::new();
+    let mut legend = HashMap::new();
+
+    for line in ascii.lines().filter(|line| !line.trim().is_empty()) {
+        let (name, value) = match line.split_once(": ") {
+            Some(field) => field,
+            None => {
+                rows.push(line);
+                continue;
+            }
+        };
+
+        let mut name_chars = name.chars();
+        match (name_chars.next(), name_chars.next()) {
+            (Some(key), None) => {
+                let objects: Vec<GridObject> = ron::from_str(value)
+                    .with_context(|| format!("Couldn't read legend entry {}", key))?;
+                legend.insert(key, objects);
+            }
+            _ => match name {
+                "origin" => origin = ron::from_str(value)?,
+                "info" => level.info = ron::from_str(value)?,
+                "rules" => level.rules = ron::from_str(value)?,
+                "palette" => level.palette = ron::from_str(value)?,
+                "goals" => level.goals = ron::from_str(value)?,
+                "rooms" => level.rooms = ron::from_str(value)?,
+                _ => bail!("Unknown field {}", name),
+            },
+        }
+    }
+
+    for (row, cells) in rows.iter().enumerate() {
+        for (column, cell) in cells.chars().enumerate() {
+            let position = GridPosition {
+                x: origin.x + column as i32,
+                y: origin.y - row as i32,
+            };
+
+            if cell == EMPTY {
+                continue;
+            }
+
+            if let Some(object) = from_glyph(cell) {
+                level.objects.push((object, position));
+                continue;
+            }
+
+            let objects = legend
+                .get(&cell)
+                .with_context(|| format!("{} at row {} isn't in the legend", cell, row + 1))?;
+            level
+                .objects
+                .extend(objects.iter().map(|object| (object.clone(), position)));
+        }
+    }
+
+    Ok(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::in_game::BlockType;
+
+    fn objects(level: &LevelData) -> Vec<(&GridObject, &GridPosition)> {
+        level
+            .objects
+            .iter()
+            .map(|(object, position)| (object, position))
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_the_shipped_level() {
+        let level = LevelData::from_ron(include_str!("../../assets/levels/level.ron")).unwrap();
+        let ascii = render_level(&level).unwrap();
+
+        let parsed = parse(&ascii).unwrap();
+        assert_eq!(parsed.objects.len(), level.objects.len());
+        assert_eq!(parsed.info.name, level.info.name);
+        assert_eq!(parsed.rooms.0.len(), level.rooms.0.len());
+        assert_eq!(render_level(&parsed).unwrap(), ascii);
+    }
+
+    #[test]
+    fn round_trips_glyphs_and_shared_cells() {
+        let ascii = "origin: (x: -1, y: 2)\n\
+            \n\
+            #@.a\n\
+            O*X<\n\
+            \n\
+            a: [Button(\"Red\"),PushBlock(kind:\"Red\",pushes_left:2)]\n";
+
+        let level = parse(ascii).unwrap();
+        assert_eq!(level.objects.len(), 8);
+        assert!(level.objects.iter().any(|(object, position)| {
+            matches!(object, GridObject::Player) && *position == GridPosition { x: 0, y: 2 }
+        }));
+        assert!(level.objects.iter().any(|(object, position)| {
+            matches!(object, GridObject::Conveyor(Direction::Left))
+                && *position == GridPosition { x: 2, y: 1 }
+        }));
+
+        let rendered = render(&objects(&level)).unwrap();
+        let reparsed = parse(&rendered).unwrap();
+        assert_eq!(reparsed.objects.len(), 8);
+        assert_eq!(render(&objects(&reparsed)).unwrap(), rendered);
+    }
+
+    #[test]
+    fn notes_runtime_state_in_comments() {
+        let block = GridObject::PushBlock {
+            kind: BlockType("Red".to_string()),
+            pushes_left: 1,
+            pullable: false,
+            discovery: None,
+            times_pushed: 2,
+            discovered: true,
+        };
+        let position = GridPosition { x: 0, y: 0 };
+
+        let ascii = render(&[(&block, &position)]).unwrap();
+        assert!(ascii.contains("// pushed 2 times, discovered"));
+
+        // The state itself isn't saved in the level, so it starts afresh
+        let level = parse(&ascii).unwrap();
+        assert!(matches!(
+            level.objects.as_slice(),
+            [(
+                GridObject::PushBlock {
+                    pushes_left: 1,
+                    times_pushed: 0,
+                    ..
+                },
+                _
+            )]
+        ));
+    }
+
+    #[test]
+    fn defaults_the_origin_to_the_top_left() {
+        let level = parse("@.\n.#").unwrap();
+        assert!(level.objects.iter().any(|(object, position)| {
+            matches!(object, GridObject::Wall) && *position == GridPosition { x: 1, y: -1 }
+        }));
+    }
+
+    #[test]
+    fn rejects_cells_missing_from_the_legend() {
+        assert!(parse("@a").is_err());
+        assert!(render(&[]).is_err());
+    }
+}
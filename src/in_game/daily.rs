@@ -0,0 +1,391 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use bevy::{prelude::*, tasks::AsyncComputeTaskPool};
+use serde::{Deserialize, Serialize};
+
+use crate::app_state::AppState;
+
+use super::generator::{self, GeneratorOptions};
+use super::storage;
+use super::undo::TurnUndoneEvent;
+use super::Cleanup;
+use super::CurrentLevel;
+use super::LevelCompleteEvent;
+use super::LevelData;
+use super::LevelInitialized;
+use super::MoveCount;
+use super::MoveSystem;
+use super::PlayerMovedEvent;
+use super::DEFAULT_LEVEL_PATH;
+
+/// Where the best result of every daily puzzle played is kept.
+const RESULTS_PATH: &str = "daily_results.ron";
+
+/// Moves shown on each row of the shareable summary.
+const SUMMARY_ROW_LENGTH: usize = 10;
+
+/// Today's puzzle whilst it's being generated. The generator can take a while, so it runs as
+/// a background task rather than holding up the frame.
+pub struct DailyGeneration {
+    day: u64,
+    level: Option<Arc<Mutex<Option<Option<LevelData>>>>>,
+}
+
+impl DailyGeneration {
+    pub fn new(day: u64) -> Self {
+        Self { day, level: None }
+    }
+}
+
+/// Shown until today's puzzle has been generated.
+#[derive(Component)]
+struct DailyLoading;
+
+/// Today's puzzle whilst it's being played, and whether each move so far pushed a block.
+pub struct DailyPuzzle {
+    day: u64,
+    moves: Vec<bool>,
+}
+
+impl DailyPuzzle {
+    pub fn new(day: u64) -> Self {
+        Self {
+            day,
+            moves: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct DailyResult {
+    moves: u32,
+    pushes: u32,
+}
+
+/// Best results keyed by day.
+type DailyResults = BTreeMap<u64, DailyResult>;
+
+/// Shows the shareable summary once the daily puzzle is complete.
+#[derive(Component)]
+struct DailySummary;
+
+pub struct DailyPlugin;
+impl Plugin for DailyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .with_system(finish_daily_generation)
+                .with_system(record_daily_moves.after(MoveSystem::Player))
+                .with_system(complete_daily)
+                .with_system(reset_daily),
+        );
+    }
+}
+
+/// Days since the Unix epoch in UTC, which seeds the daily puzzle.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn today() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs() / 86_400)
+}
+
+/// `SystemTime` isn't available on the web, so the browser's clock is used instead.
+#[cfg(target_arch = "wasm32")]
+pub fn today() -> u64 {
+    (js_sys::Date::now() / 86_400_000.0) as u64
+}
+
+/// The puzzle for `day`. Generation is seeded by the day alone, so it's the same everywhere.
+pub fn generate_level(day: u64) -> Option<LevelData> {
+    generator::generate(day, &GeneratorOptions::default()).map(|(level, _)| level)
+}
+
+/// The `year-month-day` date of a day since the Unix epoch.
+fn date(day: u64) -> String {
+    // Howard Hinnant's days to civil date algorithm
+    let days = day as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!("{}-{:02}-{:02}", year, month, day_of_month)
+}
+
+fn load_results() -> DailyResults {
+    storage::read(RESULTS_PATH)
+        .and_then(|results| ron::from_str(&results).ok())
+        .unwrap_or_default()
+}
+
+fn save_results(results: &DailyResults) -> anyhow::Result<()> {
+    storage::write(RESULTS_PATH, &ron::to_string(results)?)
+}
+
+/// The text players can paste elsewhere to share how they did, without spoiling the solution.
+fn share_text(puzzle: &DailyPuzzle, result: DailyResult, best: DailyResult) -> String {
+    let grid: Vec<String> = puzzle
+        .moves
+        .chunks(SUMMARY_ROW_LENGTH)
+        .map(|row| {
+            row.iter()
+                .map(|pushed| if *pushed { "🟧" } else { "🟩" })
+                .collect()
+        })
+        .collect();
+
+    format!(
+        "{}\n{}",
+        share_heading(puzzle, result, best),
+        grid.join("\n")
+    )
+}
+
+fn share_heading(puzzle: &DailyPuzzle, result: DailyResult, best: DailyResult) -> String {
+    format!(
+        "Daily {}\n{} moves, {} pushes (best {})",
+        date(puzzle.day),
+        result.moves,
+        result.pushes,
+        best.moves
+    )
+}
+
+/// The shareable summary as shown in game. The font has no emoji, so each move is a coloured
+/// `#` instead.
+fn summary_text(
+    puzzle: &DailyPuzzle,
+    result: DailyResult,
+    best: DailyResult,
+    font: Handle<Font>,
+) -> Text {
+    let style = |color| TextStyle {
+        font: font.clone(),
+        font_size: 25.0,
+        color,
+    };
+
+    let mut sections = vec![TextSection {
+        value: share_heading(puzzle, result, best),
+        style: style(Color::WHITE),
+    }];
+    for row in puzzle.moves.chunks(SUMMARY_ROW_LENGTH) {
+        sections.push(TextSection {
+            value: "\n".to_string(),
+            style: style(Color::WHITE),
+        });
+        for pushed in row {
+            sections.push(TextSection {
+                value: "#".to_string(),
+                style: style(if *pushed { Color::ORANGE } else { Color::GREEN }),
+            });
+        }
+    }
+
+    Text {
+        sections,
+        alignment: TextAlignment::default(),
+    }
+}
+
+/// Shows a loading message, then generates today's puzzle and swaps it in as the current level.
+/// Generation starts a frame late so the message is drawn first, as on the web the task runs on
+/// the main thread.
+fn finish_daily_generation(
+    mut commands: Commands,
+    generation: Option<ResMut<DailyGeneration>>,
+    task_pool: Res<AsyncComputeTaskPool>,
+    mut levels: ResMut<Assets<LevelData>>,
+    asset_server: Res<AssetServer>,
+    mut current_level: ResMut<CurrentLevel>,
+    loading_messages: Query<Entity, With<DailyLoading>>,
+) {
+    let mut generation = match generation {
+        Some(generation) => generation,
+        None => return,
+    };
+
+    if generation.is_added() {
+        commands
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    align_self: AlignSelf::FlexEnd,
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        bottom: Val::Percent(50.0),
+                        right: Val::Percent(35.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    "Generating today's puzzle...",
+                    TextStyle {
+                        font: asset_server.load("font/roboto_thin.ttf"),
+                        font_size: 50.0,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment::default(),
+                ),
+                ..Default::default()
+            })
+            .insert(Cleanup)
+            .insert(DailyLoading);
+        return;
+    }
+
+    let level = match &generation.level {
+        Some(level) => level.clone(),
+        None => {
+            let day = generation.day;
+            let level = Arc::new(Mutex::new(None));
+            let result = level.clone();
+            task_pool
+                .spawn(async move {
+                    let generated = generate_level(day);
+                    if let Ok(mut result) = result.lock() {
+                        *result = Some(generated);
+                    }
+                })
+                .detach();
+
+            generation.level = Some(level);
+            return;
+        }
+    };
+
+    let generated = match level.lock().ok().and_then(|mut level| level.take()) {
+        Some(generated) => generated,
+        None => return,
+    };
+
+    commands.remove_resource::<DailyGeneration>();
+    for entity in loading_messages.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    // Falls back to the usual level if today's puzzle couldn't be generated
+    match generated {
+        Some(level) => {
+            commands.insert_resource(DailyPuzzle::new(generation.day));
+            current_level.handle = levels.add(level);
+        }
+        None => {
+            current_level.handle = asset_server.load(DEFAULT_LEVEL_PATH);
+            current_level.name = DEFAULT_LEVEL_PATH.to_string();
+        }
+    }
+}
+
+fn record_daily_moves(
+    mut player_moved_events: EventReader<PlayerMovedEvent>,
+    mut turn_undone_events: EventReader<TurnUndoneEvent>,
+    move_count: Res<MoveCount>,
+    puzzle: Option<ResMut<DailyPuzzle>>,
+) {
+    let mut puzzle = match puzzle {
+        Some(puzzle) => puzzle,
+        None => return,
+    };
+
+    // Undone moves are left out of the summary
+    for _ in turn_undone_events.iter() {
+        puzzle.moves.pop();
+    }
+
+    for _ in player_moved_events.iter() {
+        let pushes_recorded = puzzle.moves.iter().filter(|pushed| **pushed).count();
+        puzzle.moves.push(move_count.pushes as usize > pushes_recorded);
+    }
+}
+
+fn complete_daily(
+    mut commands: Commands,
+    mut level_complete_events: EventReader<LevelCompleteEvent>,
+    move_count: Res<MoveCount>,
+    puzzle: Option<Res<DailyPuzzle>>,
+    asset_server: Res<AssetServer>,
+) {
+    let puzzle = match puzzle {
+        Some(puzzle) => puzzle,
+        None => return,
+    };
+
+    for _ in level_complete_events.iter() {
+        let result = DailyResult {
+            moves: move_count.moves,
+            pushes: move_count.pushes,
+        };
+
+        let mut results = load_results();
+        let best = results.entry(puzzle.day).or_insert(result);
+        if result.moves < best.moves {
+            *best = result;
+        }
+        let best = *best;
+
+        if let Err(error) = save_results(&results) {
+            eprintln!("Couldn't save the daily result: {}", error);
+        }
+
+        println!("{}", share_text(&puzzle, result, best));
+
+        commands
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    align_self: AlignSelf::FlexEnd,
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        bottom: Val::Px(10.0),
+                        left: Val::Px(10.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                text: summary_text(
+                    &puzzle,
+                    result,
+                    best,
+                    asset_server.load("font/roboto_thin.ttf"),
+                ),
+                ..Default::default()
+            })
+            .insert(Cleanup)
+            .insert(DailySummary);
+    }
+}
+
+/// Restarting the daily puzzle starts its move log again.
+fn reset_daily(
+    mut commands: Commands,
+    mut level_initialized_events: EventReader<LevelInitialized>,
+    puzzle: Option<ResMut<DailyPuzzle>>,
+    summaries: Query<Entity, With<DailySummary>>,
+) {
+    if level_initialized_events.iter().count() == 0 {
+        return;
+    }
+
+    if let Some(mut puzzle) = puzzle {
+        puzzle.moves.clear();
+    }
+
+    for entity in summaries.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,116 @@
+use bevy::prelude::*;
+
+use crate::app_state::AppState;
+
+use super::goals::Goals;
+use super::solver::Puzzle;
+use super::Cleanup;
+use super::GridObject;
+use super::GridPosition;
+use super::LevelRules;
+use super::MoveCount;
+use super::MoveSystem;
+use super::PlayerMovedEvent;
+
+/// The HUD warning shown whilst the level can no longer be solved.
+#[derive(Component)]
+struct DeadlockWarning;
+
+pub struct DeadlocksPlugin;
+impl Plugin for DeadlocksPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(AppState::InGame).with_system(create_warning))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(check_deadlocks.after(MoveSystem::Goals)),
+            );
+    }
+}
+
+fn create_warning(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let style = TextStyle {
+        font: asset_server.load("font/roboto_thin.ttf"),
+        font_size: 25.0,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                display: Display::None,
+                align_items: AlignItems::Center,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(Cleanup)
+        .insert(DeadlockWarning)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(30.0), Val::Px(30.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        margin: Rect {
+                            right: Val::Px(10.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    color: Color::ORANGE_RED.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle {
+                        text: Text::with_section("!", style.clone(), TextAlignment::default()),
+                        ..Default::default()
+                    });
+                });
+
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "Stuck - press U to undo or R to restart",
+                    style,
+                    TextAlignment::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
+
+/// Looks for blocks that can plainly never reach a button after every move.
+fn check_deadlocks(
+    mut player_moved_events: EventReader<PlayerMovedEvent>,
+    spawned_objects: Query<(), Added<GridObject>>,
+    grid_objects: Query<(&GridObject, &GridPosition)>,
+    rules: Res<LevelRules>,
+    goals: Res<Goals>,
+    move_count: Res<MoveCount>,
+    mut warnings: Query<&mut Style, With<DeadlockWarning>>,
+) {
+    // Restarts and undos respawn the level, so it's checked again once the new objects are in
+    if player_moved_events.iter().count() == 0 && spawned_objects.is_empty() {
+        return;
+    }
+
+    let is_deadlocked = match Puzzle::for_deadlocks(grid_objects.iter(), &rules, &goals) {
+        Ok(puzzle) => puzzle.is_deadlocked(&puzzle.start_after(move_count.pushes)),
+        Err(_) => false,
+    };
+
+    for mut style in warnings.iter_mut() {
+        style.display = if is_deadlocked {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+}
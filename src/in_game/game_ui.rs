@@ -4,21 +4,27 @@ use bevy::{math, prelude::*};
 
 use crate::app_state::AppState;
 
-use super::ButtonStateChangeEvent;
+use super::goals::GoalProgress;
+use super::undo::TurnUndoneEvent;
 use super::Level;
+use super::LevelCompleteEvent;
+use super::LevelFailedEvent;
 use super::LevelInitialized;
-
-struct LevelCompleteEvent;
+use super::LevelRules;
+use super::MoveCount;
 
 pub struct GameUiPlugin;
 impl Plugin for GameUiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<LevelCompleteEvent>()
-            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(create_ui))
+        app.add_system_set(SystemSet::on_enter(AppState::InGame).with_system(create_ui))
             .add_system_set(
                 SystemSet::on_update(AppState::InGame)
                     .with_system(update_ui)
-                    .with_system(level_complete_ui),
+                    .with_system(update_move_count_ui)
+                    .with_system(update_goals_ui)
+                    .with_system(level_complete_ui)
+                    .with_system(level_failed_ui)
+                    .with_system(clear_level_messages),
             );
     }
 }
@@ -59,7 +65,7 @@ fn create_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                     text: Text::with_section(
                         "x / x",
                         TextStyle {
-                            font,
+                            font: font.clone(),
                             font_size: 25.0,
                             color: Color::WHITE,
                         },
@@ -68,59 +74,122 @@ fn create_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                     ..Default::default()
                 })
                 .insert(PressedButtonsDisplay);
+
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: math::Rect {
+                            left: Val::Px(10.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 25.0,
+                            color: Color::WHITE,
+                        },
+                        TextAlignment::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(MoveCountDisplay);
         });
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: math::Rect {
+                    left: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font,
+                    font_size: 25.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(GoalsDisplay);
 }
 
 #[derive(Component)]
 struct PressedButtonsDisplay;
 
-fn update_ui(
-    mut button_state_change_event: EventReader<ButtonStateChangeEvent>,
-    mut level_initialized_event: EventReader<LevelInitialized>,
-    mut query: Query<&mut Text, With<PressedButtonsDisplay>>,
-    mut level: ResMut<Level>,
-    mut level_complete_event: EventWriter<LevelCompleteEvent>,
+/// Shows moves and pushes against the level's limits, empty when the level has none.
+#[derive(Component)]
+struct MoveCountDisplay;
+
+/// Lists the level's goals with how far along each one is.
+#[derive(Component)]
+struct GoalsDisplay;
+
+/// Text shown over the level when it ends, removed when it restarts.
+#[derive(Component)]
+struct LevelMessage;
+
+fn update_ui(level: Res<Level>, mut query: Query<&mut Text, With<PressedButtonsDisplay>>) {
+    if !level.is_changed() {
+        return;
+    }
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = format!(
+            "{} / {}",
+            &level.pressed_button_count, &level.total_button_count
+        );
+    }
+}
+
+fn update_move_count_ui(
+    move_count: Res<MoveCount>,
+    rules: Res<LevelRules>,
+    mut query: Query<&mut Text, With<MoveCountDisplay>>,
 ) {
-    fn update_text_count(
-        level: &mut Level,
-        query: &mut Query<&mut Text, With<PressedButtonsDisplay>>,
-    ) {
-        for mut text in query.iter_mut() {
-            text.sections[0].value = format!(
-                "{} / {}",
-                &level.pressed_button_count, &level.total_button_count
-            );
-        }
+    if !move_count.is_changed() && !rules.is_changed() {
+        return;
+    }
+
+    let mut limits = Vec::new();
+    if let Some(max_moves) = rules.max_moves {
+        limits.push(format!("Moves {} / {}", move_count.moves, max_moves));
+    }
+    if let Some(max_pushes) = rules.max_pushes {
+        limits.push(format!("Pushes {} / {}", move_count.pushes, max_pushes));
+    }
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = limits.join("  ");
     }
+}
 
-    for _ in level_initialized_event.iter() {
-        update_text_count(&mut level, &mut query);
+fn update_goals_ui(
+    goal_progress: Res<GoalProgress>,
+    mut query: Query<&mut Text, With<GoalsDisplay>>,
+) {
+    if !goal_progress.is_changed() {
+        return;
     }
 
-    for button_state in button_state_change_event.iter() {
-        match button_state {
-            ButtonStateChangeEvent::Pressed(_) => {
-                println!("Pressed");
-                level.pressed_button_count += 1;
-
-                if level.pressed_button_count == level.total_button_count {
-                    level_complete_event.send(LevelCompleteEvent);
-                }
-            }
-            ButtonStateChangeEvent::Unpressed(_) => {
-                println!("Unpressed (before check)");
-                if level.pressed_button_count == 0 {
-                    println!("\t count already 0");
-                    continue;
-                }
-
-                println!("Decreasing count");
-                level.pressed_button_count =
-                    level.total_button_count.min(level.pressed_button_count - 1);
-            }
-        }
-
-        update_text_count(&mut level, &mut query);
+    let goals: Vec<String> = goal_progress
+        .0
+        .iter()
+        .map(|(goal, done, total)| format!("{} {} / {}", goal.label(), done, total))
+        .collect();
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = goals.join("  ");
     }
 }
 
@@ -152,6 +221,61 @@ fn level_complete_ui(
                 TextAlignment::default(),
             ),
             ..Default::default()
-        });
+        })
+        .insert(LevelMessage);
+    }
+}
+
+fn level_failed_ui(
+    mut level_failed_event: EventReader<LevelFailedEvent>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    for event in level_failed_event.iter() {
+        let message = match event {
+            LevelFailedEvent::OutOfMoves => "Out of Moves - press U to undo or R to restart",
+            LevelFailedEvent::OutOfPushes => "Out of Pushes - press U to undo or R to restart",
+            LevelFailedEvent::Caught => "Caught - press U to undo or R to restart",
+        };
+
+        commands.spawn_bundle(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Percent(50.0),
+                    right: Val::Percent(30.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                message,
+                TextStyle {
+                    font: asset_server.load("font/roboto_thin.ttf"),
+                    font_size: 50.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(LevelMessage);
+    }
+}
+
+fn clear_level_messages(
+    mut level_initialized_event: EventReader<LevelInitialized>,
+    mut turn_undone_events: EventReader<TurnUndoneEvent>,
+    mut commands: Commands,
+    query: Query<Entity, With<LevelMessage>>,
+) {
+    let level_changed = level_initialized_event.iter().count() + turn_undone_events.iter().count();
+    if level_changed == 0 {
+        return;
+    }
+
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
     }
 }
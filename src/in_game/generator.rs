@@ -0,0 +1,218 @@
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::goals::Goals;
+use super::intro::LevelInfo;
+use super::rooms::{Room, Rooms};
+use super::solver::{Puzzle, Step};
+use super::GridObject;
+use super::GridPosition;
+use super::migration;
+use super::LevelData;
+use super::LevelRules;
+use super::Palette;
+
+/// Shapes the levels [`generate`] makes. Difficulty is the length of the shortest solution.
+pub struct GeneratorOptions {
+    /// The largest the room can be, walls not included.
+    pub width: i32,
+    pub height: i32,
+    /// How many block and button pairs to place, at most one per palette colour.
+    pub colours: usize,
+    pub max_pushes_left: u32,
+    pub min_moves: usize,
+    pub max_moves: usize,
+    /// How many states the solver may visit before a level is thrown away as too hard to check.
+    pub max_states: usize,
+    pub attempts: u32,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        Self {
+            width: 7,
+            height: 6,
+            colours: 2,
+            max_pushes_left: 3,
+            min_moves: 10,
+            max_moves: 40,
+            max_states: 200_000,
+            attempts: 500,
+        }
+    }
+}
+
+/// Generates a level the solver has proven solvable within the target difficulty, along with
+/// its shortest solution. The same seed and options always give the same level.
+pub fn generate(seed: u64, options: &GeneratorOptions) -> Option<(LevelData, Vec<Step>)> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let palette = Palette::default();
+    let rules = LevelRules::default();
+    let goals = Goals::default();
+
+    for _ in 0..options.attempts {
+        let objects = generate_objects(&mut rng, options, &palette);
+
+        let puzzle = match Puzzle::new(
+            objects.iter().map(|(object, position)| (object, position)),
+            &rules,
+            &goals,
+        ) {
+            Ok(puzzle) => puzzle,
+            Err(_) => continue,
+        };
+
+        let solution = match puzzle.solve(options.max_states) {
+            Some(solution) => solution,
+            None => continue,
+        };
+
+        if (options.min_moves..=options.max_moves).contains(&solution.len()) {
+            let rooms = Rooms(vec![Room {
+                origin: GridPosition { x: -1, y: -1 },
+                width: options.width + 2,
+                height: options.height + 2,
+            }]);
+
+            let level = LevelData {
+                version: migration::LATEST_VERSION,
+                info: LevelInfo::default(),
+                rules,
+                palette,
+                goals,
+                rooms,
+                objects,
+            };
+
+            return Some((level, solution));
+        }
+    }
+
+    None
+}
+
+fn generate_objects(
+    rng: &mut ChaCha8Rng,
+    options: &GeneratorOptions,
+    palette: &Palette,
+) -> Vec<(GridObject, GridPosition)> {
+    // Two overlapping rectangles give L, T and cross shaped rooms as well as plain ones
+    let mut floor = Vec::new();
+    for _ in 0..2 {
+        let width = rng.gen_range(3..=options.width);
+        let height = rng.gen_range(3..=options.height);
+        let left = rng.gen_range(0..=options.width - width);
+        let bottom = rng.gen_range(0..=options.height - height);
+
+        for y in bottom..bottom + height {
+            for x in left..left + width {
+                let position = GridPosition { x, y };
+                if !floor.contains(&position) {
+                    floor.push(position);
+                }
+            }
+        }
+    }
+
+    // A few pillars make the room awkward to push around in
+    for _ in 0..floor.len() / 10 {
+        // usize is a different width on the web, so sample as u32 to get the same level there
+        let pillar = rng.gen_range(0..floor.len() as u32) as usize;
+        floor.swap_remove(pillar);
+    }
+
+    let mut objects = Vec::new();
+
+    // Wall off every cell touching the floor, including diagonally, so the room is sealed
+    for y in -1..=options.height {
+        for x in -1..=options.width {
+            let position = GridPosition { x, y };
+            let touches_floor = floor
+                .iter()
+                .any(|floor| (floor.x - x).abs() <= 1 && (floor.y - y).abs() <= 1);
+
+            if touches_floor && !floor.contains(&position) {
+                objects.push((GridObject::Wall, position));
+            }
+        }
+    }
+
+    floor.shuffle(rng);
+    let mut cells = floor.into_iter();
+
+    if let Some(position) = cells.next() {
+        objects.push((GridObject::Player, position));
+    }
+
+    for entry in palette.0.iter().take(options.colours) {
+        let (button, block) = match (cells.next(), cells.next()) {
+            (Some(button), Some(block)) => (button, block),
+            _ => break,
+        };
+
+        objects.push((GridObject::Button(entry.id.clone(), None), button));
+        objects.push((
+            GridObject::PushBlock {
+                kind: entry.id.clone(),
+                pushes_left: rng.gen_range(1..=options.max_pushes_left),
+                pullable: false,
+                discovery: None,
+                times_pushed: 0,
+                discovered: false,
+            },
+            block,
+        ));
+    }
+
+    objects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::in_game::ascii;
+
+    #[test]
+    fn generates_the_same_level_for_the_same_seed() {
+        let options = GeneratorOptions::default();
+        let (first, first_solution) = generate(7, &options).unwrap();
+        let (second, second_solution) = generate(7, &options).unwrap();
+
+        assert_eq!(
+            ascii::render_level(&first).unwrap(),
+            ascii::render_level(&second).unwrap()
+        );
+        assert_eq!(first_solution, second_solution);
+    }
+
+    #[test]
+    fn pins_the_level_for_a_seed() {
+        let (level, solution) = generate(1, &GeneratorOptions::default()).unwrap();
+
+        let objects: Vec<_> = level
+            .objects
+            .iter()
+            .map(|(object, position)| (object, position))
+            .collect();
+
+        // Changing this level changes every daily puzzle, so it should only happen on purpose
+        assert_eq!(
+            ascii::render(&objects).unwrap(),
+            "origin: (x: -1, y: 5)\n\
+            \n\
+            .#####.\n\
+            ##a..##\n\
+            #...b.#\n\
+            #..#..#\n\
+            #.@.c.#\n\
+            #.d...#\n\
+            #######\n\
+            \n\
+            a: [Button(\"Green\")]\n\
+            b: [PushBlock(kind:\"Red\",pushes_left:1,pullable:false,discovery:None)]\n\
+            c: [PushBlock(kind:\"Green\",pushes_left:3,pullable:false,discovery:None)]\n\
+            d: [Button(\"Red\")]\n"
+        );
+        assert_eq!(solution.len(), 22);
+    }
+}
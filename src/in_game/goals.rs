@@ -0,0 +1,232 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::app_state::AppState;
+
+use super::GridObject;
+use super::GridPosition;
+use super::Level;
+use super::LevelCompleteEvent;
+use super::LevelFailedEvent;
+use super::LevelOutcome;
+use super::LevelRules;
+use super::EXIT_ACTIVE_COLOR;
+use super::EXIT_INACTIVE_COLOR;
+use super::MoveCount;
+use super::MoveSystem;
+use super::PlayerMovedEvent;
+
+/// Something the player has to achieve to complete a level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Goal {
+    /// Hold down every coloured button and pressure plate.
+    AllButtons,
+    /// Stand a character on an exit tile. Exits only open once every other goal is met.
+    ReachExit,
+    /// Pick up every gem.
+    CollectAllGems,
+    /// Push every block until it runs out of pushes.
+    AllBlocksExhausted,
+}
+
+impl Goal {
+    pub fn label(self) -> &'static str {
+        match self {
+            Goal::AllButtons => "Buttons",
+            Goal::ReachExit => "Exit",
+            Goal::CollectAllGems => "Gems",
+            Goal::AllBlocksExhausted => "Spent blocks",
+        }
+    }
+
+    /// How far along the goal is, as `(done, total)`.
+    pub fn progress(self, objects: &[(&GridObject, &GridPosition)]) -> (u32, u32) {
+        let count = |filter: &dyn Fn(&GridObject) -> bool| {
+            objects.iter().filter(|(object, _)| filter(object)).count() as u32
+        };
+
+        match self {
+            Goal::AllButtons => {
+                let level = Level::count(objects.iter().map(|(object, _)| *object));
+                (level.pressed_button_count, level.total_button_count)
+            }
+            Goal::ReachExit => {
+                let exits: Vec<&GridPosition> = objects
+                    .iter()
+                    .filter(|(object, _)| matches!(object, GridObject::Exit))
+                    .map(|(_, position)| *position)
+                    .collect();
+
+                let is_reached = objects.iter().any(|(object, position)| {
+                    matches!(object, GridObject::Player) && exits.contains(position)
+                });
+
+                (is_reached as u32, 1)
+            }
+            Goal::CollectAllGems => (
+                count(&|object| matches!(object, GridObject::Gem { collected: true })),
+                count(&|object| matches!(object, GridObject::Gem { .. })),
+            ),
+            Goal::AllBlocksExhausted => (
+                count(&|object| matches!(object, GridObject::PushBlock { pushes_left: 0, .. })),
+                count(&|object| matches!(object, GridObject::PushBlock { .. })),
+            ),
+        }
+    }
+}
+
+/// The goals a level is completed by, all of which have to be met.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Goals(pub Vec<Goal>);
+
+impl Default for Goals {
+    fn default() -> Self {
+        Self(vec![Goal::AllButtons])
+    }
+}
+
+/// Each of the level's goals with its `(done, total)` progress.
+#[derive(Default)]
+pub struct GoalProgress(pub Vec<(Goal, u32, u32)>);
+
+pub struct GoalsPlugin;
+impl Plugin for GoalsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GoalProgress>().add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .with_system(
+                    collect_gems
+                        .label(MoveSystem::Buttons)
+                        .after(MoveSystem::Enemies),
+                )
+                .with_system(
+                    update_goal_progress
+                        .label(MoveSystem::Goals)
+                        .after(MoveSystem::Buttons)
+                        .after(MoveSystem::Doors),
+                )
+                .with_system(check_level_outcome.after(MoveSystem::Goals))
+                .with_system(light_exits.after(MoveSystem::Goals)),
+        );
+    }
+}
+
+fn collect_gems(
+    mut player_moved_events: EventReader<PlayerMovedEvent>,
+    mut grid_objects: Query<(&mut GridObject, &GridPosition, &mut Visibility)>,
+) {
+    if player_moved_events.iter().count() == 0 {
+        return;
+    }
+
+    let characters: Vec<GridPosition> = grid_objects
+        .iter()
+        .filter(|(object, _, _)| matches!(object, GridObject::Player))
+        .map(|(_, position, _)| *position)
+        .collect();
+
+    for (mut object, position, mut visibility) in grid_objects.iter_mut() {
+        if let GridObject::Gem { collected } = object.as_mut() {
+            if !*collected && characters.contains(position) {
+                *collected = true;
+                visibility.is_visible = false;
+            }
+        }
+    }
+}
+
+fn update_goal_progress(
+    goals: Res<Goals>,
+    grid_objects: Query<(&GridObject, &GridPosition)>,
+    mut goal_progress: ResMut<GoalProgress>,
+) {
+    let objects: Vec<(&GridObject, &GridPosition)> = grid_objects.iter().collect();
+    let mut progress: Vec<(Goal, u32, u32)> = goals
+        .0
+        .iter()
+        .map(|goal| {
+            let (done, total) = goal.progress(&objects);
+            (*goal, done, total)
+        })
+        .collect();
+
+    // Standing on an exit doesn't count until it has opened
+    if !exits_open(&progress) {
+        for (goal, done, _) in progress.iter_mut() {
+            if *goal == Goal::ReachExit {
+                *done = 0;
+            }
+        }
+    }
+
+    // Only touch the resource when something changed so the HUD can rely on change detection
+    if goal_progress.0 != progress {
+        goal_progress.0 = progress;
+    }
+}
+
+/// Whether every goal other than reaching an exit is met.
+fn exits_open(progress: &[(Goal, u32, u32)]) -> bool {
+    progress
+        .iter()
+        .filter(|(goal, _, _)| *goal != Goal::ReachExit)
+        .all(|(_, done, total)| done >= total)
+}
+
+/// Whether every goal that needs blocks pushing is met, so running out of pushes is no loss.
+fn blocks_in_place(progress: &[(Goal, u32, u32)]) -> bool {
+    progress
+        .iter()
+        .filter(|(goal, _, _)| matches!(goal, Goal::AllButtons | Goal::AllBlocksExhausted))
+        .all(|(_, done, total)| done >= total)
+}
+
+fn light_exits(
+    goal_progress: Res<GoalProgress>,
+    spawned_objects: Query<(), Added<GridObject>>,
+    exits: Query<(&GridObject, &Handle<ColorMaterial>)>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    if !goal_progress.is_changed() && spawned_objects.is_empty() {
+        return;
+    }
+
+    let color = if exits_open(&goal_progress.0) {
+        EXIT_ACTIVE_COLOR
+    } else {
+        EXIT_INACTIVE_COLOR
+    };
+
+    for (object, material) in exits.iter() {
+        if let (GridObject::Exit, Some(material)) = (object, materials.get_mut(material)) {
+            material.color = color;
+        }
+    }
+}
+
+fn check_level_outcome(
+    mut player_moved_events: EventReader<PlayerMovedEvent>,
+    mut level_complete_events: EventWriter<LevelCompleteEvent>,
+    mut level_failed_events: EventWriter<LevelFailedEvent>,
+    goal_progress: Res<GoalProgress>,
+    move_count: Res<MoveCount>,
+    rules: Res<LevelRules>,
+    mut outcome: ResMut<LevelOutcome>,
+) {
+    // Levels can only be completed or failed by making a move
+    if player_moved_events.iter().count() == 0 || *outcome != LevelOutcome::Playing {
+        return;
+    }
+
+    if goal_progress.0.iter().all(|(_, done, total)| done >= total) {
+        *outcome = LevelOutcome::Complete;
+        level_complete_events.send(LevelCompleteEvent);
+    } else if move_count.out_of_moves(&rules) {
+        *outcome = LevelOutcome::Failed;
+        level_failed_events.send(LevelFailedEvent::OutOfMoves);
+    } else if move_count.out_of_pushes(&rules) && !blocks_in_place(&goal_progress.0) {
+        *outcome = LevelOutcome::Failed;
+        level_failed_events.send(LevelFailedEvent::OutOfPushes);
+    }
+}
@@ -0,0 +1,432 @@
+use std::sync::{Arc, Mutex};
+
+use bevy::{prelude::*, tasks::AsyncComputeTaskPool};
+
+use crate::app_state::AppState;
+
+use super::arrow_mesh;
+use super::goals::Goals;
+use super::save::SaveData;
+use super::solver::{Puzzle, Search, State, Step};
+use super::undo::TurnUndoneEvent;
+use super::CharacterIndex;
+use super::Cleanup;
+use super::CurrentLevel;
+use super::Grid;
+use super::GridObject;
+use super::GridPosition;
+use super::LevelInitialized;
+use super::LevelOutcome;
+use super::LevelRules;
+use super::MoveCount;
+use super::MoveSystem;
+use super::PlayerMovedEvent;
+
+const HINTS_PER_LEVEL: u32 = 3;
+
+/// How many states a hint may search before giving up, keeping the game responsive.
+const MAX_HINT_STATES: usize = 200_000;
+
+const HINT_COLOR: Color = Color::YELLOW;
+
+/// The arrow, block outlines and message showing a hint, removed once a move is made.
+#[derive(Component)]
+struct HintMarker;
+
+/// Tells the player about the hint key, on levels where there are hints to give.
+#[derive(Component)]
+struct HintPrompt;
+
+/// Whether the solver can model the level in play. Hints are hidden on levels it can't.
+#[derive(Default)]
+struct HintsAvailable(bool);
+
+/// The next move, and the blocks it shifts, or why there's no hint to give.
+type Hint = Result<(Step, Vec<usize>), &'static str>;
+
+/// A hint whilst it's being searched for. The search can take a while, so it runs as a
+/// background task rather than holding up the frame.
+struct HintSearch {
+    /// The level as it stood when the hint was asked for, until the search starts.
+    level: Option<(Puzzle, State, u32)>,
+    hint: Option<Arc<Mutex<Option<Hint>>>>,
+}
+
+pub struct HintsPlugin;
+impl Plugin for HintsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(AppState::InGame).with_system(create_prompt))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(start_hint_search)
+                    .with_system(finish_hint_search.after(MoveSystem::Player))
+                    .with_system(clear_hints)
+                    .with_system(update_prompt),
+            );
+    }
+}
+
+fn create_prompt(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(HintsAvailable::default());
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("font/roboto_thin.ttf"),
+                    font_size: 25.0,
+                    color: HINT_COLOR,
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(Cleanup)
+        .insert(HintPrompt);
+}
+
+fn hints_used(save: &SaveData, current_level: &CurrentLevel) -> u32 {
+    save.hints_used
+        .get(&current_level.name)
+        .copied()
+        .unwrap_or(0)
+}
+
+/// Checks whether hints can be given whenever the level is spawned, including after undo.
+#[allow(clippy::too_many_arguments)]
+fn update_prompt(
+    spawned_objects: Query<(), Added<GridObject>>,
+    grid_objects: Query<(&GridObject, &GridPosition)>,
+    rules: Res<LevelRules>,
+    goals: Res<Goals>,
+    save: Res<SaveData>,
+    current_level: Res<CurrentLevel>,
+    mut hints_available: ResMut<HintsAvailable>,
+    mut prompts: Query<(&mut Text, &mut Style), With<HintPrompt>>,
+) {
+    if !spawned_objects.is_empty() {
+        let is_available = Puzzle::new(grid_objects.iter(), &rules, &goals).is_ok();
+        if hints_available.0 != is_available {
+            hints_available.0 = is_available;
+        }
+    }
+
+    if !hints_available.is_changed() && !save.is_changed() {
+        return;
+    }
+
+    let hints_left = HINTS_PER_LEVEL.saturating_sub(hints_used(&save, &current_level));
+    for (mut text, mut style) in prompts.iter_mut() {
+        text.sections[0].value = format!("H for a hint ({} left)", hints_left);
+        style.display = if hints_available.0 {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+}
+
+/// Solves the level from `state`, having already made `moves` moves, for the next move.
+fn find_hint(puzzle: &Puzzle, state: &State, moves: u32) -> Hint {
+    let step = match puzzle.solve_from(state, moves, MAX_HINT_STATES) {
+        Search::Solved(steps) => steps.first().copied().ok_or("No hint available")?,
+        Search::Unsolvable => {
+            return Err("This can't be solved any more - press U to undo or R to restart")
+        }
+        Search::GaveUp => return Err("No hint available"),
+    };
+
+    let moved_blocks = match puzzle.apply(state, step) {
+        Some(next) => state.moved_blocks(&next).collect(),
+        None => Vec::new(),
+    };
+
+    Ok((step, moved_blocks))
+}
+
+/// The level's objects with characters numbered the same way as when swapping between them.
+fn ordered_objects<'a>(
+    grid_objects: &'a Query<(&GridObject, &GridPosition, Option<&CharacterIndex>)>,
+) -> Vec<(&'a GridObject, &'a GridPosition)> {
+    let mut objects: Vec<_> = grid_objects.iter().collect();
+    objects.sort_by_key(|(_, _, index)| index.copied());
+    objects
+        .into_iter()
+        .map(|(object, position, _)| (object, position))
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn start_hint_search(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    grid_objects: Query<(&GridObject, &GridPosition, Option<&CharacterIndex>)>,
+    hint_markers: Query<Entity, With<HintMarker>>,
+    search: Option<Res<HintSearch>>,
+    rules: Res<LevelRules>,
+    goals: Res<Goals>,
+    move_count: Res<MoveCount>,
+    outcome: Res<LevelOutcome>,
+    current_level: Res<CurrentLevel>,
+    hints_available: Res<HintsAvailable>,
+    save: Res<SaveData>,
+    asset_server: Res<AssetServer>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::H)
+        || *outcome != LevelOutcome::Playing
+        || !hints_available.0
+        || search.is_some()
+    {
+        return;
+    }
+
+    for entity in hint_markers.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    // Hints that can't help don't use up the allowance
+    if hints_used(&save, &current_level) >= HINTS_PER_LEVEL {
+        spawn_hint_message(
+            &mut commands,
+            &asset_server,
+            "No hints left for this level".to_string(),
+        );
+        return;
+    }
+
+    let puzzle = match Puzzle::new(ordered_objects(&grid_objects), &rules, &goals) {
+        Ok(puzzle) => puzzle,
+        Err(_) => {
+            spawn_hint_message(
+                &mut commands,
+                &asset_server,
+                "Hints aren't available for this level".to_string(),
+            );
+            return;
+        }
+    };
+    let state = puzzle.start_after(move_count.pushes);
+
+    commands.insert_resource(HintSearch {
+        level: Some((puzzle, state, move_count.moves)),
+        hint: None,
+    });
+    spawn_hint_message(&mut commands, &asset_server, "Thinking...".to_string());
+}
+
+/// Searches for the hint asked for, then shows it. The search starts a frame late so the
+/// thinking message is drawn first, as on the web the task runs on the main thread. Anything
+/// changing the level in the meantime drops the search.
+#[allow(clippy::too_many_arguments)]
+fn finish_hint_search(
+    mut commands: Commands,
+    search: Option<ResMut<HintSearch>>,
+    task_pool: Res<AsyncComputeTaskPool>,
+    mut player_moved_events: EventReader<PlayerMovedEvent>,
+    mut level_initialized_events: EventReader<LevelInitialized>,
+    mut turn_undone_events: EventReader<TurnUndoneEvent>,
+    grid_objects: Query<(&GridObject, &GridPosition, Option<&CharacterIndex>)>,
+    hint_markers: Query<Entity, With<HintMarker>>,
+    current_level: Res<CurrentLevel>,
+    mut save: ResMut<SaveData>,
+    grid: Res<Grid>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+) {
+    let level_changed = player_moved_events.iter().count()
+        + level_initialized_events.iter().count()
+        + turn_undone_events.iter().count();
+
+    let mut search = match search {
+        Some(search) => search,
+        None => return,
+    };
+
+    if level_changed > 0 {
+        commands.remove_resource::<HintSearch>();
+        return;
+    }
+
+    if search.is_added() {
+        return;
+    }
+
+    if search.hint.is_none() {
+        if let Some((puzzle, state, moves)) = search.level.take() {
+            let hint = Arc::new(Mutex::new(None));
+            let result = hint.clone();
+            task_pool
+                .spawn(async move {
+                    let found = find_hint(&puzzle, &state, moves);
+                    if let Ok(mut result) = result.lock() {
+                        *result = Some(found);
+                    }
+                })
+                .detach();
+
+            search.hint = Some(hint);
+        }
+        return;
+    }
+
+    let found = search
+        .hint
+        .as_ref()
+        .and_then(|hint| hint.lock().ok().and_then(|mut hint| hint.take()));
+    let hint = match found {
+        Some(hint) => hint,
+        None => return,
+    };
+
+    commands.remove_resource::<HintSearch>();
+    for entity in hint_markers.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let (step, moved_blocks) = match hint {
+        Ok(hint) => hint,
+        Err(message) => {
+            spawn_hint_message(&mut commands, &asset_server, message.to_string());
+            return;
+        }
+    };
+
+    let hints_used = hints_used(&save, &current_level);
+    save.hints_used
+        .insert(current_level.name.clone(), hints_used + 1);
+    if let Err(error) = save.save() {
+        eprintln!("Couldn't save hints used: {}", error);
+    }
+
+    let objects = ordered_objects(&grid_objects);
+    let characters: Vec<&GridPosition> = objects
+        .iter()
+        .filter(|(object, _)| matches!(object, GridObject::Player))
+        .map(|(_, position)| *position)
+        .collect();
+    let blocks: Vec<&GridPosition> = objects
+        .iter()
+        .filter(|(object, _)| matches!(object, GridObject::PushBlock { .. }))
+        .map(|(_, position)| *position)
+        .collect();
+
+    // An arrow on the edge of the character's cell points the way to go
+    let position = characters[step.character];
+    let (x, y) = step.direction.offset();
+    commands
+        .spawn_bundle(ColorMesh2dBundle {
+            mesh: meshes.add(arrow_mesh()).into(),
+            material: materials.add(HINT_COLOR.into()),
+            transform: Transform::from_scale(Vec3::splat(24.0))
+                .with_rotation(step.direction.rotation())
+                .with_translation(Vec3::new(
+                    (position.x as f32 + x as f32 * 0.5) * grid.cell_size,
+                    (position.y as f32 + y as f32 * 0.5) * grid.cell_size,
+                    150.0,
+                )),
+            ..Default::default()
+        })
+        .insert(Cleanup)
+        .insert(HintMarker);
+
+    // An outline behind each block shows which ones the move shifts
+    for block in &moved_blocks {
+        let position = blocks[*block];
+        commands
+            .spawn_bundle(ColorMesh2dBundle {
+                mesh: meshes.add(shape::Quad::default().into()).into(),
+                material: materials.add(HINT_COLOR.into()),
+                transform: Transform::from_scale(Vec3::splat(grid.cell_size + 8.0))
+                    .with_translation(Vec3::new(
+                        position.x as f32 * grid.cell_size,
+                        position.y as f32 * grid.cell_size,
+                        98.0,
+                    )),
+                ..Default::default()
+            })
+            .insert(Cleanup)
+            .insert(HintMarker);
+    }
+
+    let action = if moved_blocks.is_empty() {
+        "Move"
+    } else {
+        "Shift the highlighted block"
+    };
+    let character = if characters.len() > 1 {
+        format!(" with character {}", step.character + 1)
+    } else {
+        String::new()
+    };
+
+    spawn_hint_message(
+        &mut commands,
+        &asset_server,
+        format!(
+            "Hint: {} {:?}{} ({} left)",
+            action,
+            step.direction,
+            character,
+            HINTS_PER_LEVEL - hints_used - 1
+        ),
+    );
+}
+
+fn spawn_hint_message(commands: &mut Commands, asset_server: &AssetServer, message: String) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(50.0),
+                    left: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                message,
+                TextStyle {
+                    font: asset_server.load("font/roboto_thin.ttf"),
+                    font_size: 25.0,
+                    color: HINT_COLOR,
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(Cleanup)
+        .insert(HintMarker);
+}
+
+fn clear_hints(
+    mut commands: Commands,
+    mut player_moved_events: EventReader<PlayerMovedEvent>,
+    mut level_initialized_events: EventReader<LevelInitialized>,
+    mut turn_undone_events: EventReader<TurnUndoneEvent>,
+    hint_markers: Query<Entity, With<HintMarker>>,
+) {
+    let level_changed = player_moved_events.iter().count()
+        + level_initialized_events.iter().count()
+        + turn_undone_events.iter().count();
+    if level_changed == 0 {
+        return;
+    }
+
+    for entity in hint_markers.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
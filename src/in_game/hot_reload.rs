@@ -0,0 +1,164 @@
+use std::{fs, path::Path};
+
+use bevy::{
+    asset::{AssetServerSettings, LoadState},
+    prelude::*,
+};
+
+use crate::app_state::AppState;
+
+use super::xsb::{self, XsbOptions};
+use super::Cleanup;
+use super::CurrentLevel;
+use super::GridPosition;
+use super::LevelData;
+
+/// Explains why the level file couldn't be loaded, whilst the last good version stays in play.
+#[derive(Component)]
+struct LoadErrorOverlay;
+
+#[derive(Component)]
+struct LoadErrorText;
+
+/// Reloads the level in place whenever its file is saved, in development builds.
+pub struct HotReloadPlugin;
+impl Plugin for HotReloadPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(watch_for_changes)
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(create_overlay))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(reload_level)
+                    .with_system(show_load_errors),
+            );
+    }
+}
+
+fn watch_for_changes(asset_server: Res<AssetServer>) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+
+    if let Err(error) = asset_server.watch_for_changes() {
+        eprintln!("Couldn't watch assets for changes: {}", error);
+    }
+}
+
+fn create_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..Default::default()
+                },
+                max_size: Size::new(Val::Percent(80.0), Val::Auto),
+                padding: Rect::all(Val::Px(10.0)),
+                ..Default::default()
+            },
+            color: Color::rgba(0.5, 0.0, 0.0, 0.85).into(),
+            ..Default::default()
+        })
+        .insert(Cleanup)
+        .insert(LoadErrorOverlay)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: asset_server.load("font/roboto_thin.ttf"),
+                            font_size: 25.0,
+                            color: Color::WHITE,
+                        },
+                        TextAlignment::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(LoadErrorText);
+        });
+}
+
+/// Respawns the level from its new version, the same way a restart does.
+fn reload_level(
+    mut commands: Commands,
+    mut level_events: EventReader<AssetEvent<LevelData>>,
+    level_objects: Query<Entity, With<GridPosition>>,
+    mut current_level: ResMut<CurrentLevel>,
+) {
+    let modified = level_events.iter().any(
+        |event| matches!(event, AssetEvent::Modified { handle } if *handle == current_level.handle),
+    );
+    if !modified || !current_level.spawned {
+        return;
+    }
+
+    for entity in level_objects.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    current_level.spawned = false;
+}
+
+/// Loader errors only reach the log, so the file is read again to find out what's wrong.
+fn load_error(asset_folder: &str, path: &Path) -> String {
+    let contents = match fs::read_to_string(Path::new(asset_folder).join(path)) {
+        Ok(contents) => contents,
+        Err(error) => return error.to_string(),
+    };
+
+    let result = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("xsb" | "sok") => xsb::parse_xsb(&contents, &XsbOptions::default()).map(|_| ()),
+        _ => LevelData::from_ron(&contents).map(|_| ()),
+    };
+
+    match result {
+        Err(error) => format!("{:#}", error),
+        Ok(()) => "It has been fixed since, and will load shortly".to_string(),
+    }
+}
+
+fn show_load_errors(
+    asset_server: Res<AssetServer>,
+    asset_settings: Res<AssetServerSettings>,
+    current_level: Res<CurrentLevel>,
+    mut last_load_state: Local<Option<LoadState>>,
+    mut overlays: Query<&mut Style, With<LoadErrorOverlay>>,
+    mut texts: Query<&mut Text, With<LoadErrorText>>,
+) {
+    let load_state = asset_server.get_load_state(&current_level.handle);
+    // A new overlay is made each time the game starts, so it needs setting up again
+    if *last_load_state == Some(load_state) && !current_level.is_changed() {
+        return;
+    }
+    *last_load_state = Some(load_state);
+
+    let display = match load_state {
+        LoadState::Failed => Display::Flex,
+        LoadState::Loaded => Display::None,
+        _ => return,
+    };
+
+    if load_state == LoadState::Failed {
+        let path = asset_server.get_handle_path(&current_level.handle);
+        let message = match path {
+            Some(path) => format!(
+                "Couldn't load {}: {}",
+                path.path().display(),
+                load_error(&asset_settings.asset_folder, path.path())
+            ),
+            None => "Couldn't load the level".to_string(),
+        };
+
+        for mut text in texts.iter_mut() {
+            text.sections[0].value = message.clone();
+        }
+    }
+
+    for mut style in overlays.iter_mut() {
+        style.display = display;
+    }
+}
@@ -0,0 +1,180 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::app_state::AppState;
+
+use super::Cleanup;
+use super::CurrentLevel;
+use super::LevelOutcome;
+
+/// Optional details about a level, shown on its intro card before play starts.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelInfo {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    /// A short introduction, such as how a new rule works.
+    pub intro: Option<String>,
+    /// Moves a good solution takes.
+    pub par: Option<u32>,
+    pub tags: Vec<String>,
+    pub theme: Option<String>,
+}
+
+impl LevelInfo {
+    /// Whether there's anything worth showing on an intro card.
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.author.is_none()
+            && self.intro.is_none()
+            && self.par.is_none()
+            && self.tags.is_empty()
+            && self.theme.is_none()
+    }
+
+    /// A single line describing the level for menus, or none if there's nothing to say.
+    pub fn summary(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        match (&self.name, &self.author) {
+            (Some(name), Some(author)) => parts.push(format!("{} by {}", name, author)),
+            (Some(name), None) => parts.push(name.clone()),
+            (None, Some(author)) => parts.push(format!("by {}", author)),
+            (None, None) => {}
+        }
+        if let Some(par) = self.par {
+            parts.push(format!("Par {}", par));
+        }
+        parts.extend(self.theme.iter().chain(self.tags.iter()).cloned());
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(" · "))
+        }
+    }
+}
+
+#[derive(Component)]
+struct IntroCard;
+
+pub struct IntroPlugin;
+impl Plugin for IntroPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .with_system(show_intro_card)
+                .with_system(start_level),
+        );
+    }
+}
+
+/// Shows the card whilst the level is waiting to start, replacing any left from a restart.
+fn show_intro_card(
+    mut commands: Commands,
+    outcome: Res<LevelOutcome>,
+    info: Res<LevelInfo>,
+    asset_server: Res<AssetServer>,
+    cards: Query<Entity, With<IntroCard>>,
+) {
+    if !outcome.is_changed() {
+        return;
+    }
+
+    for entity in cards.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    if *outcome != LevelOutcome::Intro {
+        return;
+    }
+
+    let font = asset_server.load("font/roboto_thin.ttf");
+    let text_style = |font_size| TextStyle {
+        font: font.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+
+    let mut lines = Vec::new();
+    if let Some(name) = &info.name {
+        lines.push((name.clone(), text_style(60.0)));
+    }
+    if let Some(author) = &info.author {
+        lines.push((format!("by {}", author), text_style(25.0)));
+    }
+    if let Some(intro) = &info.intro {
+        lines.push((intro.clone(), text_style(30.0)));
+    }
+    if let Some(par) = info.par {
+        lines.push((format!("Par {} moves", par), text_style(25.0)));
+    }
+
+    let tags: Vec<&str> = info
+        .theme
+        .iter()
+        .chain(info.tags.iter())
+        .map(String::as_str)
+        .collect();
+    if !tags.is_empty() {
+        lines.push((tags.join(" · "), text_style(20.0)));
+    }
+    lines.push(("Press Enter to start".to_string(), text_style(25.0)));
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(Cleanup)
+        .insert(IntroCard)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::ColumnReverse,
+                        align_items: AlignItems::Center,
+                        max_size: Size::new(Val::Percent(60.0), Val::Auto),
+                        padding: Rect::all(Val::Px(30.0)),
+                        ..Default::default()
+                    },
+                    color: UiColor(Color::rgba(0.0, 0.0, 0.0, 0.85)),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    for (line, style) in lines {
+                        parent.spawn_bundle(TextBundle {
+                            style: Style {
+                                margin: Rect {
+                                    bottom: Val::Px(15.0),
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            },
+                            text: Text::with_section(line, style, TextAlignment::default()),
+                            ..Default::default()
+                        });
+                    }
+                });
+        });
+}
+
+fn start_level(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut outcome: ResMut<LevelOutcome>,
+    mut current_level: ResMut<CurrentLevel>,
+) {
+    if *outcome != LevelOutcome::Intro || !keyboard_input.just_pressed(KeyCode::Return) {
+        return;
+    }
+
+    *outcome = LevelOutcome::Playing;
+    // Restarts skip the card
+    current_level.intro_seen = true;
+}
@@ -0,0 +1,68 @@
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::*,
+};
+
+use super::xsb::{self, XsbOptions};
+use super::LevelData;
+
+/// Loads `LevelData` from the RON files under `assets/levels`.
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let level_data = LevelData::from_ron(std::str::from_utf8(bytes)?)?;
+            load_context.set_default_asset(LoadedAsset::new(level_data));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
+
+/// Loads Sokoban levels from XSB files with the default import options. The first level in
+/// the file is the default asset and the rest are labelled by their number, from `2`.
+#[derive(Default)]
+pub struct XsbLoader;
+
+impl AssetLoader for XsbLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let levels = xsb::parse_xsb(std::str::from_utf8(bytes)?, &XsbOptions::default())?;
+            for (number, level_data) in levels.into_iter().enumerate() {
+                if number == 0 {
+                    load_context.set_default_asset(LoadedAsset::new(level_data));
+                } else {
+                    load_context
+                        .set_labeled_asset(&(number + 1).to_string(), LoadedAsset::new(level_data));
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["xsb", "sok"]
+    }
+}
+
+pub struct LevelLoaderPlugin;
+impl Plugin for LevelLoaderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<LevelData>()
+            .init_asset_loader::<LevelLoader>()
+            .init_asset_loader::<XsbLoader>();
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::solver::{Puzzle, State};
+use super::BlockType;
+
+/// How many pushes a block has against how many the shortest solution spends on it.
+pub struct PushBudget {
+    pub kind: BlockType,
+    pub pushes_left: u32,
+    pub pushes_used: u32,
+}
+
+/// Numbers for ordering levels by how hard they're likely to be.
+pub struct Difficulty {
+    pub solution_moves: usize,
+    pub solution_pushes: usize,
+    /// Average moves available from each reachable state.
+    pub branching_factor: f32,
+    pub reachable_states: usize,
+    /// Reachable states from which the level can no longer be solved. None if the search gave
+    /// up, since states at its edge would look dead without being so.
+    pub dead_states: Option<usize>,
+    /// False if the search gave up before visiting every reachable state, so the count of
+    /// reachable states is only a lower bound.
+    pub fully_explored: bool,
+    pub budgets: Vec<PushBudget>,
+}
+
+/// Analyses `puzzle`, visiting at most `max_states` states. Returns none if it can't be solved.
+/// The level's move limit is ignored whilst counting states.
+pub fn analyse(puzzle: &Puzzle, max_states: usize) -> Option<Difficulty> {
+    let solution = puzzle.solve(max_states)?;
+
+    let mut solution_pushes = 0;
+    let mut pushes_used = vec![0; puzzle.block_count()];
+    let mut state = puzzle.start().clone();
+    for step in &solution {
+        let next = puzzle
+            .apply(&state, *step)
+            .expect("Solution contains a blocked move");
+
+        let mut pushed = false;
+        for block in state.moved_blocks(&next) {
+            pushes_used[block] += 1;
+            pushed = true;
+        }
+
+        solution_pushes += pushed as usize;
+        state = next;
+    }
+
+    let budgets = pushes_used
+        .into_iter()
+        .enumerate()
+        .map(|(block, pushes_used)| PushBudget {
+            kind: puzzle.block_kind(block).clone(),
+            pushes_left: puzzle.start().pushes_left(block),
+            pushes_used,
+        })
+        .collect();
+
+    // Map out every reachable state, then walk backwards from the solved ones to find which
+    // states can still lead to a solution
+    let mut indices: HashMap<State, usize> = HashMap::new();
+    let mut states = vec![puzzle.start().clone()];
+    let mut predecessors: Vec<Vec<usize>> = vec![Vec::new()];
+    let mut queue = VecDeque::from([0]);
+    let mut moves_available = 0;
+    let mut fully_explored = true;
+    indices.insert(puzzle.start().clone(), 0);
+
+    while let Some(current) = queue.pop_front() {
+        let successors = puzzle.successors(&states[current]);
+        moves_available += successors.len();
+
+        for (_, next) in successors {
+            let next = match indices.get(&next) {
+                Some(index) => *index,
+                None if states.len() >= max_states => {
+                    fully_explored = false;
+                    continue;
+                }
+                None => {
+                    indices.insert(next.clone(), states.len());
+                    states.push(next);
+                    predecessors.push(Vec::new());
+                    queue.push_back(states.len() - 1);
+                    states.len() - 1
+                }
+            };
+
+            predecessors[next].push(current);
+        }
+    }
+
+    let mut can_be_solved = vec![false; states.len()];
+    let mut queue: VecDeque<usize> = (0..states.len())
+        .filter(|state| puzzle.is_solved(&states[*state]))
+        .collect();
+    for state in &queue {
+        can_be_solved[*state] = true;
+    }
+
+    while let Some(current) = queue.pop_front() {
+        for previous in &predecessors[current] {
+            if !can_be_solved[*previous] {
+                can_be_solved[*previous] = true;
+                queue.push_back(*previous);
+            }
+        }
+    }
+
+    Some(Difficulty {
+        solution_moves: solution.len(),
+        solution_pushes,
+        branching_factor: moves_available as f32 / states.len() as f32,
+        reachable_states: states.len(),
+        dead_states: fully_explored
+            .then(|| can_be_solved.iter().filter(|solvable| !**solvable).count()),
+        fully_explored,
+        budgets,
+    })
+}
@@ -0,0 +1,269 @@
+use anyhow::{bail, Context};
+use serde::Deserialize;
+
+/// The level file version this build reads and writes.
+pub const LATEST_VERSION: u32 = 1;
+
+/// Each migration upgrades a level file from the version at its index to the next one.
+const MIGRATIONS: [fn(&str) -> anyhow::Result<String>; LATEST_VERSION as usize] = [upgrade_v0];
+
+/// Just enough of a level file to find its version. Files from before versioning are version 0.
+#[derive(Deserialize)]
+struct VersionHeader {
+    #[serde(default)]
+    version: u32,
+}
+
+pub fn version(ron: &str) -> anyhow::Result<u32> {
+    let header: VersionHeader = ron::from_str(ron)?;
+    Ok(header.version)
+}
+
+/// Runs every migration between the level file's version and the latest. The version field
+/// itself is left as it was.
+pub fn migrate(ron: &str) -> anyhow::Result<String> {
+    let version = version(ron)?;
+    if version > LATEST_VERSION {
+        bail!(
+            "Level version {} is newer than this game supports ({})",
+            version,
+            LATEST_VERSION
+        );
+    }
+
+    let mut migrated = ron.to_string();
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migrated = migration(&migrated)
+            .with_context(|| format!("Couldn't upgrade level from version {}", from))?;
+    }
+
+    Ok(migrated)
+}
+
+fn is_identifier(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+/// The index just after the comment, string or char literal starting at `start`, if there is
+/// one. Comments are checked first so apostrophes in them aren't read as char literals.
+fn skip_ignored(bytes: &[u8], start: usize) -> Option<usize> {
+    let rest = &bytes[start..];
+
+    if rest.starts_with(b"//") {
+        let end = rest.iter().position(|byte| *byte == b'\n');
+        return Some(end.map_or(bytes.len(), |end| start + end + 1));
+    }
+
+    if rest.starts_with(b"/*") {
+        let end = rest.windows(2).skip(2).position(|pair| pair == b"*/");
+        return Some(end.map_or(bytes.len(), |end| start + end + 4));
+    }
+
+    let quote = bytes[start];
+    if quote != b'"' && quote != b'\'' {
+        return None;
+    }
+
+    let mut index = start + 1;
+    while index < bytes.len() && bytes[index] != quote {
+        if bytes[index] == b'\\' {
+            index += 1;
+        }
+        index += 1;
+    }
+
+    Some((index + 1).min(bytes.len()))
+}
+
+fn skip_whitespace(bytes: &[u8], start: usize) -> usize {
+    bytes[start..]
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .map_or(bytes.len(), |offset| start + offset)
+}
+
+/// Whether the identifier `name` starts at `index` and is followed by `next`, ignoring spaces.
+/// Returns the index just after `next`.
+fn identifier_then(bytes: &[u8], index: usize, name: &[u8], next: u8) -> Option<usize> {
+    let is_name = bytes[index..].starts_with(name)
+        && (index == 0 || !is_identifier(bytes[index - 1]))
+        && !matches!(bytes.get(index + name.len()), Some(byte) if is_identifier(*byte));
+    if !is_name {
+        return None;
+    }
+
+    let after = skip_whitespace(bytes, index + name.len());
+    match bytes.get(after) {
+        Some(byte) if *byte == next => Some(after + 1),
+        _ => None,
+    }
+}
+
+/// Finds the first top level comma and the closing bracket of the arguments starting at `start`.
+fn argument_bounds(bytes: &[u8], start: usize) -> anyhow::Result<(Option<usize>, usize)> {
+    let mut depth = 0;
+    let mut comma = None;
+    let mut index = start;
+
+    while index < bytes.len() {
+        if let Some(end) = skip_ignored(bytes, index) {
+            index = end;
+            continue;
+        }
+
+        match bytes[index] {
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' if depth == 0 => return Ok((comma, index)),
+            b')' | b']' | b'}' => depth -= 1,
+            b',' if depth == 0 && comma.is_none() => comma = Some(index),
+            _ => {}
+        }
+        index += 1;
+    }
+
+    bail!("Unclosed arguments at {}", start)
+}
+
+/// Version 0 covers every file from before versioning, whether from before or after block
+/// colours moved into the palette.
+fn upgrade_v0(ron: &str) -> anyhow::Result<String> {
+    drop_button_entities(&quote_block_types(ron))
+}
+
+/// Block colours were once a fixed enum, written bare as in `kind: Red`. They're palette ids
+/// now, so `kind: Red`, `Button(Red, None)` and `Paint(Red)` get quoted.
+fn quote_block_types(ron: &str) -> String {
+    let bytes = ron.as_bytes();
+    let mut migrated = String::with_capacity(ron.len());
+    let mut copied = 0;
+    let mut index = 0;
+
+    while index < bytes.len() {
+        if let Some(end) = skip_ignored(bytes, index) {
+            index = end;
+            continue;
+        }
+
+        let value = identifier_then(bytes, index, b"kind", b':')
+            .or_else(|| identifier_then(bytes, index, b"Button", b'('))
+            .or_else(|| identifier_then(bytes, index, b"Paint", b'('));
+        let start = match value {
+            Some(value) => skip_whitespace(bytes, value),
+            None => {
+                index += 1;
+                continue;
+            }
+        };
+
+        let end = bytes[start..]
+            .iter()
+            .position(|byte| !is_identifier(*byte))
+            .map_or(bytes.len(), |length| start + length);
+        let is_bare = end > start && bytes[start].is_ascii_alphabetic();
+        if is_bare {
+            migrated.push_str(&ron[copied..start]);
+            migrated.push('"');
+            migrated.push_str(&ron[start..end]);
+            migrated.push('"');
+            copied = end;
+        }
+        index = end.max(index + 1);
+    }
+
+    migrated.push_str(&ron[copied..]);
+    migrated
+}
+
+/// Version 0 saved which entity was pressing each button, always `None` in a level file, so
+/// `Button("Red", None)` becomes `Button("Red")`.
+fn drop_button_entities(ron: &str) -> anyhow::Result<String> {
+    let bytes = ron.as_bytes();
+    let mut migrated = String::with_capacity(ron.len());
+    let mut copied = 0;
+    let mut index = 0;
+
+    while index < bytes.len() {
+        if let Some(end) = skip_ignored(bytes, index) {
+            index = end;
+            continue;
+        }
+
+        let arguments = match identifier_then(bytes, index, b"Button", b'(') {
+            Some(arguments) => arguments,
+            None => {
+                index += 1;
+                continue;
+            }
+        };
+
+        let (comma, close) = argument_bounds(bytes, arguments)?;
+        if let Some(comma) = comma {
+            migrated.push_str(ron[copied..comma].trim_end());
+            copied = close;
+        }
+        index = close + 1;
+    }
+
+    migrated.push_str(&ron[copied..]);
+    Ok(migrated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::in_game::LevelData;
+
+    #[test]
+    fn loads_the_original_level_file() {
+        let level = LevelData::from_ron(include_str!("../../assets/levels/level.ron.bak")).unwrap();
+        assert_eq!(level.version, LATEST_VERSION);
+        assert_eq!(level.objects.len(), 8);
+    }
+
+    #[test]
+    fn loads_the_shipped_level_file() {
+        LevelData::from_ron(include_str!("../../assets/levels/level.ron")).unwrap();
+    }
+
+    #[test]
+    fn upgrades_palette_ids_and_bare_block_types() {
+        let v0 = r#"(objects: [
+            (Button(Red, None), (x: 0, y: 0)),
+            (Button("Green" , None), (x: 1, y: 0)),
+            (Paint(Blue), (x: 2, y: 0)),
+            (PushBlock(kind: Red, pushes_left: 3), (x: 3, y: 0)),
+        ])"#;
+
+        let migrated = migrate(v0).unwrap();
+        assert!(migrated.contains(r#"Button("Red")"#));
+        assert!(migrated.contains(r#"Button("Green")"#));
+        assert!(migrated.contains(r#"Paint("Blue")"#));
+        assert!(migrated.contains(r#"kind: "Red""#));
+        LevelData::from_ron(v0).unwrap();
+    }
+
+    #[test]
+    fn leaves_comments_and_strings_alone() {
+        let v0 = "(objects: [\n\
+            // the player's Button(Red, None) is in a comment\n\
+            (Button(Red, None), (x: 0, y: 0)),\n\
+            (PushBlock(kind: Red, pushes_left: 3), (x: 1, y: 0)),\n\
+        ])";
+
+        let migrated = migrate(v0).unwrap();
+        assert!(migrated.contains("// the player's Button(Red, None) is in a comment"));
+        assert!(migrated.contains(r#"(Button("Red"), (x: 0, y: 0))"#));
+        assert_eq!(LevelData::from_ron(v0).unwrap().objects.len(), 2);
+    }
+
+    #[test]
+    fn latest_files_are_unchanged() {
+        let v1 = r#"(version: 1, objects: [(Button("Red"), (x: 0, y: 0))])"#;
+        assert_eq!(migrate(v1).unwrap(), v1);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        assert!(migrate("(version: 99, objects: [])").is_err());
+    }
+}
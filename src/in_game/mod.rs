@@ -1,52 +1,410 @@
+pub mod ascii;
+mod daily;
+mod deadlocks;
 mod game_ui;
+pub mod generator;
+mod goals;
+mod hints;
+mod hot_reload;
+pub mod intro;
+mod level_loader;
+pub mod metrics;
+pub mod migration;
+mod replay;
+mod rooms;
+mod save;
+pub mod solver;
+mod storage;
+mod undo;
+pub mod xsb;
 
 
 
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+};
 use bevy_inspector_egui::{Inspectable, RegisterInspectable};
 use serde::{Serialize, Deserialize};
 
 use crate::app_state::*;
 
+use goals::Goals;
+use intro::LevelInfo;
+use rooms::Rooms;
+use save::SaveData;
+use undo::UndoHistory;
+
 pub struct InGameStatePlugin;
 impl Plugin for InGameStatePlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<Grid>()
+            .init_resource::<ColorblindMode>()
             .add_plugin(game_ui::GameUiPlugin)
+            .add_plugin(level_loader::LevelLoaderPlugin)
+            .add_plugin(goals::GoalsPlugin)
+            .add_plugin(rooms::RoomsPlugin)
+            .add_plugin(daily::DailyPlugin)
+            .add_plugin(hints::HintsPlugin)
+            .add_plugin(intro::IntroPlugin)
+            .add_plugin(hot_reload::HotReloadPlugin)
+            .add_plugin(deadlocks::DeadlocksPlugin)
+            .add_plugin(ascii::AsciiPlugin)
+            .add_plugin(undo::UndoPlugin)
+            .add_plugin(replay::ReplayPlugin)
             .register_inspectable::<Grid>()
             .register_inspectable::<GridPosition>()
             .add_event::<PlayerMoveEvent>()
+            .add_event::<PlayerMovedEvent>()
+            .add_event::<LevelCompleteEvent>()
+            .add_event::<LevelFailedEvent>()
             .add_event::<BlockMoveEvent>()
+            .add_event::<BlockDiscoveredEvent>()
+            .add_event::<CharacterMoveEvent>()
+            .add_event::<WallHitEvent>()
             .add_event::<ButtonStateChangeEvent>()
             .add_event::<LevelInitialized>()
             .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(on_enter))
             .add_system_set(
                 SystemSet::on_update(AppState::InGame)
+                    .with_system(spawn_loaded_level)
                     .with_system(update_player_keyboard)
-                    .with_system(player_move_event_listener)
+                    .with_system(swap_active_character)
+                    .with_system(toggle_colorblind_mode)
+                    .with_system(apply_colorblind_mode)
+                    .with_system(player_move_event_listener.label(MoveSystem::Player))
                     .with_system(apply_grid_entity_position)
-                    .with_system(block_move_event_listener)
-                    .with_system(decrease_pushes_remaining),
+                    .with_system(
+                        damage_tiles
+                            .after(MoveSystem::Conveyor)
+                            .before(MoveSystem::Enemies),
+                    )
+                    .with_system(
+                        advance_conveyors
+                            .label(MoveSystem::Conveyor)
+                            .after(MoveSystem::Player),
+                    )
+                    .with_system(
+                        move_enemies
+                            .label(MoveSystem::Enemies)
+                            .after(MoveSystem::Conveyor),
+                    )
+                    .with_system(
+                        update_pressure_plates
+                            .label(MoveSystem::Buttons)
+                            .after(MoveSystem::Enemies),
+                    )
+                    .with_system(restart_level)
+                    .with_system(update_level_counts.after(MoveSystem::Buttons))
+                    .with_system(
+                        paint_blocks
+                            .label(MoveSystem::Paint)
+                            .after(MoveSystem::Enemies),
+                    )
+                    .with_system(
+                        decrease_pushes_remaining
+                            .label(MoveSystem::PushCount)
+                            .after(MoveSystem::Paint),
+                    )
+                    .with_system(
+                        discover_blocks
+                            .label(MoveSystem::Discover)
+                            .after(MoveSystem::PushCount),
+                    )
+                    .with_system(
+                        block_move_event_listener
+                            .label(MoveSystem::Buttons)
+                            .after(MoveSystem::Discover),
+                    ),
             )
             .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(on_exit));
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
-enum BlockType {
-    Red,
-    Green,
-    Blue,
+/// Block moves are resolved in this order so buttons always see a block's final state.
+#[derive(SystemLabel, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum MoveSystem {
+    Player,
+    /// The world's turn, after the player's move has resolved.
+    Conveyor,
+    Enemies,
+    Paint,
+    PushCount,
+    Discover,
+    Buttons,
+    Doors,
+    Goals,
+}
+
+/// A block colour, referring to an entry in the level's `Palette` by id.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BlockType(String);
+
+impl std::fmt::Display for BlockType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct PaletteEntry {
+    id: BlockType,
+    color: Color,
+    /// Drawn over the colour in colourblind mode.
+    symbol: char,
 }
 
-#[derive(Component, Debug, Serialize)]
-enum GridObject {
+/// Every block colour a level can use. All colour rendering goes through here.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+struct Palette(Vec<PaletteEntry>);
+
+impl Palette {
+    fn get(&self, kind: &BlockType) -> Option<&PaletteEntry> {
+        self.0.iter().find(|entry| entry.id == *kind)
+    }
+
+    /// Colours missing from the palette are drawn magenta so they stand out.
+    fn color(&self, kind: &BlockType) -> Color {
+        self.get(kind).map_or(Color::FUCHSIA, |entry| entry.color)
+    }
+
+    fn symbol(&self, kind: &BlockType) -> char {
+        self.get(kind).map_or('?', |entry| entry.symbol)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        let entry = |id: &str, color, symbol| PaletteEntry {
+            id: BlockType(id.to_string()),
+            color,
+            symbol,
+        };
+
+        Self(vec![
+            entry("Red", Color::ORANGE_RED, 'R'),
+            entry("Green", Color::SEA_GREEN, 'G'),
+            entry("Blue", Color::ALICE_BLUE, 'B'),
+        ])
+    }
+}
+
+/// Shows each colour's palette symbol on top of it.
+#[derive(Default)]
+struct ColorblindMode(bool);
+
+/// Text showing a colour's palette symbol, only visible in colourblind mode.
+#[derive(Component)]
+struct ColorSymbol;
+
+fn color_symbol_bundle(
+    palette: &Palette,
+    kind: &BlockType,
+    font: Handle<Font>,
+    transform: Transform,
+) -> Text2dBundle {
+    Text2dBundle {
+        text: Text::with_section(
+            palette.symbol(kind),
+            TextStyle {
+                font,
+                font_size: 30.0,
+                color: Color::BLACK,
+            },
+            TextAlignment {
+                vertical: VerticalAlign::Center,
+                horizontal: HorizontalAlign::Center,
+            },
+        ),
+        transform,
+        visibility: Visibility { is_visible: false },
+        ..Default::default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    fn offset(self) -> (i32, i32) {
+        match self {
+            Direction::Up => (0, 1),
+            Direction::Down => (0, -1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+        }
+    }
+
+    /// Rotation turning something that points up to point this way instead.
+    fn rotation(self) -> Quat {
+        Quat::from_rotation_z(match self {
+            Direction::Up => 0.0,
+            Direction::Left => std::f32::consts::FRAC_PI_2,
+            Direction::Down => std::f32::consts::PI,
+            Direction::Right => -std::f32::consts::FRAC_PI_2,
+        })
+    }
+}
+
+/// When a block's colour is revealed, letting it press buttons of that colour.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Discovery {
+    /// Revealed once the block runs out of pushes.
+    OutOfPushes,
+    /// Revealed after the block has been pushed this many times.
+    AfterPushes(u32),
+    /// Revealed once the block is next to a button of its colour.
+    NextToButton,
+    /// Revealed from the start, but the block can't be moved once it runs out of pushes.
+    Known,
+}
+
+/// Something that can try to move into a cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mover {
     Player,
-    PushBlock { kind: BlockType, pushes_left: u32 },
-    Button(BlockType, Option<Entity>),
+    Block,
+    Enemy,
+}
+
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+pub enum GridObject {
+    Player,
+    /// `pullable` blocks can also be dragged behind the player whilst the grab key is held.
+    /// `discovery` overrides the level's discovery rule for this block.
+    PushBlock {
+        kind: BlockType,
+        pushes_left: u32,
+        #[serde(default)]
+        pullable: bool,
+        #[serde(default)]
+        discovery: Option<Discovery>,
+        #[serde(skip)]
+        times_pushed: u32,
+        #[serde(skip)]
+        discovered: bool,
+    },
+    /// Holds the entity pressing it, if any.
+    Button(BlockType, #[serde(skip)] Option<Entity>),
     Wall,
+    /// Floor tile that can only be entered whilst travelling in `direction`.
+    /// `player` and `blocks` choose which movers the restriction applies to.
+    OneWay {
+        direction: Direction,
+        player: bool,
+        blocks: bool,
+    },
+    /// Floor tile that recolours any block pushed onto it.
+    Paint(BlockType),
+    /// Wall that breaks after blocks are pushed into it `durability` times.
+    BreakableWall { durability: u32 },
+    /// Floor tile that crumbles into a `Pit` after the player steps off it `durability` times.
+    CrumblingFloor { durability: u32 },
+    Pit,
+    /// Grey plate held down by any block or character, counting towards the level's buttons.
+    /// It stays pressed for `hold_moves` moves after being released.
+    PressurePlate {
+        #[serde(default)]
+        hold_moves: u32,
+        #[serde(skip)]
+        pressed: bool,
+        #[serde(skip)]
+        release_moves_left: u32,
+    },
+    /// Floor tile that carries whatever stands on it one cell in `direction` after every move.
+    Conveyor(Direction),
+    Gem {
+        #[serde(skip)]
+        collected: bool,
+    },
+    /// Finishes the level when a character stands on it, once every other goal is met.
+    Exit,
+    /// Leads to `to`, usually in another room, sending characters there after they step on it.
+    Door { to: GridPosition },
+    /// Walks one step of `path` after every move, looping back to the start at the end.
+    /// It waits whilst its next cell is blocked, and fails the level by stepping onto a character.
+    Enemy {
+        path: Vec<Direction>,
+        #[serde(skip)]
+        step: usize,
+    },
+}
+
+impl GridObject {
+    /// Whether this object can currently be pushed or pulled.
+    fn is_movable(&self, rules: &LevelRules) -> bool {
+        match self {
+            GridObject::PushBlock {
+                pushes_left,
+                discovery,
+                ..
+            } => discovery.unwrap_or(rules.discovery) != Discovery::Known || *pushes_left > 0,
+            _ => false,
+        }
+    }
+
+    /// Whether `mover` may enter this object's cell whilst travelling in `direction`.
+    fn allows_entry(&self, mover: Mover, direction: (i32, i32)) -> bool {
+        match self {
+            GridObject::Player
+            | GridObject::PushBlock { .. }
+            | GridObject::Wall
+            | GridObject::BreakableWall { .. }
+            | GridObject::Pit
+            | GridObject::Enemy { .. } => false,
+            GridObject::Button(_, _)
+            | GridObject::Paint(_)
+            | GridObject::CrumblingFloor { .. }
+            | GridObject::PressurePlate { .. }
+            | GridObject::Conveyor(_)
+            | GridObject::Gem { .. }
+            | GridObject::Exit
+            | GridObject::Door { .. } => true,
+            GridObject::OneWay {
+                direction: allowed,
+                player,
+                blocks,
+            } => {
+                // Enemies follow the same tiles as blocks
+                let restricted = match mover {
+                    Mover::Player => *player,
+                    Mover::Block | Mover::Enemy => *blocks,
+                };
+
+                !restricted || allowed.offset() == direction
+            }
+        }
+    }
 }
 
+/// Marks the character that player input currently controls.
+#[derive(Component)]
+struct ActiveCharacter;
+
+/// Where a character comes in the level file, which sets the order Tab cycles through them.
+#[derive(Component, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+struct CharacterIndex(usize);
+
+const PIT_COLOR: Color = Color::BLACK;
+
+const EXIT_INACTIVE_COLOR: Color = Color::DARK_GREEN;
+const EXIT_ACTIVE_COLOR: Color = Color::LIME_GREEN;
+
+const ACTIVE_CHARACTER_COLOR: Color = Color::ORANGE;
+const INACTIVE_CHARACTER_COLOR: Color = Color::rgb(0.5, 0.32, 0.0);
+
+/// The sprite revealing a block's colour once it has run out of pushes.
+#[derive(Component)]
+struct ColorLabel;
+
 #[derive(Inspectable)]
 struct Grid {
     cell_size: f32,
@@ -55,31 +413,209 @@ struct Grid {
 #[derive(Component)]
 struct Cleanup;
 
+/// How many of the level's buttons and pressure plates exist and are held down.
+#[derive(Default, PartialEq, Eq)]
 struct Level {
     pub pressed_button_count: u32,
     pub total_button_count: u32,
 }
 
+impl Level {
+    fn count<'a>(objects: impl IntoIterator<Item = &'a GridObject>) -> Self {
+        let mut level = Level::default();
+
+        for object in objects {
+            let pressed = match object {
+                GridObject::Button(_, pressing_entity) => pressing_entity.is_some(),
+                GridObject::PressurePlate { pressed, .. } => *pressed,
+                _ => continue,
+            };
+
+            level.total_button_count += 1;
+            level.pressed_button_count += pressed as u32;
+        }
+
+        level
+    }
+}
+
 struct LevelInitialized;
 
-#[derive(Serialize)]
-struct LevelData {
+#[derive(Serialize, Deserialize, TypeUuid)]
+#[uuid = "e875e870-4d09-4f2d-8fa5-4255bf7483ff"]
+pub struct LevelData {
+    /// The file format version, upgraded by `migration` when the level is loaded.
+    #[serde(default)]
+    version: u32,
+    #[serde(default)]
+    info: LevelInfo,
+    #[serde(default)]
+    rules: LevelRules,
+    #[serde(default)]
+    palette: Palette,
+    #[serde(default)]
+    goals: Goals,
+    #[serde(default)]
+    rooms: Rooms,
     objects: Vec<(GridObject, GridPosition)>,
 }
 
-struct PlayerMoveEvent(i32, i32);
+/// Optional per level rule tweaks, inserted as a resource when the level is spawned.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelRules {
+    /// The longest row of blocks the player can push at once.
+    max_push_chain: u32,
+    /// How blocks without a rule of their own are discovered.
+    discovery: Discovery,
+    /// Discovering a block also discovers every other block of its colour.
+    reveal_matching: bool,
+    /// The level is failed once the player has made this many moves without completing it.
+    max_moves: Option<u32>,
+    /// Pushes and pulls the player can make across all blocks.
+    max_pushes: Option<u32>,
+}
+
+impl Default for LevelRules {
+    fn default() -> Self {
+        Self {
+            max_push_chain: 1,
+            discovery: Discovery::OutOfPushes,
+            reveal_matching: false,
+            max_moves: None,
+            max_pushes: None,
+        }
+    }
+}
+
+pub const DEFAULT_LEVEL_PATH: &str = "levels/level.ron";
+
+/// The level being played, spawned once its asset has finished loading.
+struct CurrentLevel {
+    handle: Handle<LevelData>,
+    /// Identifies the level in the save file.
+    name: String,
+    spawned: bool,
+    /// The intro card has been dismissed, so restarts go straight into play.
+    intro_seen: bool,
+}
+
+struct PlayerMoveEvent {
+    pub direction: (i32, i32),
+    /// Pull a block behind the player along with them
+    pub grab: bool,
+}
+
+/// The active character took a turn, either moving or wearing down a wall.
+struct PlayerMovedEvent {
+    /// The index of the character that took the turn.
+    pub character: usize,
+    pub direction: (i32, i32),
+    pub grab: bool,
+    /// Where the character moved from, or none if it stayed put.
+    pub from: Option<GridPosition>,
+}
+
+struct LevelCompleteEvent;
+
+enum LevelFailedEvent {
+    /// The player ran out of moves before completing the level.
+    OutOfMoves,
+    /// The level's push budget ran out with blocks still needing to be moved.
+    OutOfPushes,
+    /// An enemy stepped onto a character.
+    Caught,
+}
+
+/// Whether the level is still being played. Moves can only be made whilst it is.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum LevelOutcome {
+    /// The intro card is showing and play hasn't started yet.
+    Intro,
+    #[default]
+    Playing,
+    Complete,
+    Failed,
+}
+
+/// Moves and pushes made so far, checked against the level's limits.
+#[derive(Clone, Default)]
+struct MoveCount {
+    pub moves: u32,
+    pub pushes: u32,
+}
+
+impl MoveCount {
+    fn out_of_moves(&self, rules: &LevelRules) -> bool {
+        matches!(rules.max_moves, Some(max_moves) if self.moves >= max_moves)
+    }
+
+    fn out_of_pushes(&self, rules: &LevelRules) -> bool {
+        matches!(rules.max_pushes, Some(max_pushes) if self.pushes >= max_pushes)
+    }
+}
 
 struct BlockMoveEvent {
     pub block: Entity,
     pub position: (i32, i32),
+    /// False when the level moved the block by itself, which doesn't use up its pushes.
+    pub pushed: bool,
 }
 
-#[derive(Component, Inspectable, Clone, Copy, Serialize)]
-struct GridPosition {
+/// A character left `from`, whether by moving or by being carried, wearing down any
+/// crumbling floor there.
+struct CharacterMoveEvent {
+    pub from: GridPosition,
+}
+
+/// A block was pushed into a breakable wall without moving, wearing the wall down.
+struct WallHitEvent(Entity);
+
+/// A block's colour was revealed without it necessarily moving.
+struct BlockDiscoveredEvent {
+    pub block: Entity,
+    pub position: (i32, i32),
+}
+
+#[derive(Component, Inspectable, Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct GridPosition {
     pub x: i32,
     pub y: i32,
 }
 
+impl GridPosition {
+    fn offset(self, (x, y): (i32, i32)) -> Self {
+        Self {
+            x: self.x + x,
+            y: self.y + y,
+        }
+    }
+}
+
+/// Returns true if every object at `position` lets `mover` in whilst travelling in `direction`.
+fn is_passable<'a>(
+    objects: impl IntoIterator<Item = (&'a GridObject, &'a GridPosition)>,
+    position: GridPosition,
+    mover: Mover,
+    direction: (i32, i32),
+) -> bool {
+    objects
+        .into_iter()
+        .filter(|(_, object_position)| **object_position == position)
+        .all(|(object, _)| object.allows_entry(mover, direction))
+}
+
+/// Whether `position` holds a crumbling floor that becomes a pit as soon as a character leaves.
+fn crumbles_when_left<'a>(
+    objects: impl IntoIterator<Item = (&'a GridObject, &'a GridPosition)>,
+    position: GridPosition,
+) -> bool {
+    objects.into_iter().any(|(object, object_position)| {
+        *object_position == position
+            && matches!(object, GridObject::CrumblingFloor { durability } if *durability <= 1)
+    })
+}
+
 impl Default for Grid {
     fn default() -> Self {
         Self { cell_size: 64.0 }
@@ -91,62 +627,40 @@ enum ButtonStateChangeEvent {
     Unpressed(Entity),
 }
 
-fn on_enter(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    asset_server: Res<AssetServer>,
-    mut level_initialized_event: EventWriter<LevelInitialized>,
-) {
-    let level_data = LevelData {
-        objects: vec![
-            (GridObject::Player, GridPosition { x: 0, y: 0 }),
-            (GridObject::Wall, GridPosition { x: 3, y: 3 }),
-            (
-                GridObject::Button(BlockType::Red, None),
-                GridPosition { x: -2, y: -2 },
-            ),
-            (
-                GridObject::Button(BlockType::Green, None),
-                GridPosition { x: 0, y: -2 },
-            ),
-            (
-                GridObject::Button(BlockType::Blue, None),
-                GridPosition { x: 2, y: -2 },
-            ),
-            (
-                GridObject::PushBlock {
-                    kind: BlockType::Red,
-                    pushes_left: 3,
-                },
-                GridPosition { x: -2, y: 2 },
-            ),
-            (
-                GridObject::PushBlock {
-                    kind: BlockType::Green,
-                    pushes_left: 3,
-                },
-                GridPosition { x: 0, y: 2 },
-            ),
-            (
-                GridObject::PushBlock {
-                    kind: BlockType::Blue,
-                    pushes_left: 3,
-                },
-                GridPosition { x: 2, y: 2 },
-            ),
-        ],
-    };
-
-    let as_ron = ron::to_string(&level_data).unwrap();
-    println!("{}", as_ron);
+fn on_enter(mut commands: Commands, asset_server: Res<AssetServer>, game_mode: Res<GameMode>) {
+    commands.remove_resource::<daily::DailyPuzzle>();
+    commands.remove_resource::<daily::DailyGeneration>();
 
-    level_data.spawn(&mut commands, &mut meshes, &mut materials, &asset_server);
+    // Today's puzzle is generated in the background, and its handle filled in once it's ready
+    let (handle, name) = match *game_mode {
+        GameMode::Daily => {
+            let day = daily::today();
+            commands.insert_resource(daily::DailyGeneration::new(day));
+            (Handle::default(), format!("daily/{}", day))
+        }
+        GameMode::Normal => (
+            asset_server.load(DEFAULT_LEVEL_PATH),
+            DEFAULT_LEVEL_PATH.to_string(),
+        ),
+    };
 
-    commands.insert_resource(Level {
-        pressed_button_count: 0,
-        total_button_count: 3,
+    commands.insert_resource(CurrentLevel {
+        handle,
+        name,
+        spawned: false,
+        intro_seen: false,
     });
+    commands.insert_resource(SaveData::load());
+    commands.insert_resource(LevelInfo::default());
+    commands.insert_resource(LevelRules::default());
+    commands.insert_resource(Palette::default());
+    commands.insert_resource(Goals::default());
+    commands.insert_resource(Rooms::default());
+    commands.insert_resource(MoveCount::default());
+    commands.insert_resource(UndoHistory::default());
+    commands.insert_resource(LevelOutcome::default());
+
+    commands.insert_resource(Level::default());
 
     commands
         .spawn()
@@ -155,115 +669,569 @@ fn on_enter(
         .insert(Transform {
             ..Default::default()
         });
+}
 
-    // Bagckground grid
-    commands
-        .spawn()
-        .insert(Cleanup)
-        .insert(GlobalTransform::default())
-        .insert(Transform::default())
-        .with_children(|parent| {
-            for y in -16..16 {
-                for x in -16..16 {
-                    // TODO WT: Spawn a background for the actual level
-                    parent.spawn_bundle(ColorMesh2dBundle {
-                        mesh: meshes.add(shape::Quad::default().into()).into(),
-                        material: materials.add(Color::GRAY.into()),
-                        transform: Transform::from_scale(Vec3::splat(63.0))
-                            .with_translation(Vec3::new(x as f32 * 64.0, y as f32 * 64.0, 0.0)),
-                        ..Default::default()
+fn spawn_loaded_level(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    levels: Res<Assets<LevelData>>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut level_initialized_event: EventWriter<LevelInitialized>,
+) {
+    if current_level.spawned {
+        return;
+    }
+
+    if let Some(level_data) = levels.get(&current_level.handle) {
+        level_data.spawn(&mut commands, &mut meshes, &mut materials, &asset_server, 0);
+        commands.insert_resource(level_data.rules.clone());
+        commands.insert_resource(level_data.palette.clone());
+        commands.insert_resource(level_data.goals.clone());
+        commands.insert_resource(level_data.rooms.clone());
+        commands.insert_resource(level_data.info.clone());
+        commands.insert_resource(MoveCount::default());
+        commands.insert_resource(UndoHistory::default());
+        commands.insert_resource(if current_level.intro_seen || level_data.info.is_empty() {
+            LevelOutcome::Playing
+        } else {
+            LevelOutcome::Intro
+        });
+        current_level.spawned = true;
+
+        level_initialized_event.send(LevelInitialized);
+    }
+}
+
+fn on_exit(mut commands: Commands, query: Query<Entity, With<Cleanup>>) {
+    for e in query.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn player_move_event_listener(
+    mut listener: EventReader<PlayerMoveEvent>,
+    mut grid_objects: Query<(&GridObject, &mut GridPosition, Entity)>,
+    active_character: Query<Entity, With<ActiveCharacter>>,
+    character_indices: Query<&CharacterIndex>,
+    mut history: ResMut<UndoHistory>,
+    mut block_move_events: EventWriter<BlockMoveEvent>,
+    mut character_move_events: EventWriter<CharacterMoveEvent>,
+    mut wall_hit_events: EventWriter<WallHitEvent>,
+    mut player_moved_events: EventWriter<PlayerMovedEvent>,
+    mut move_count: ResMut<MoveCount>,
+    rules: Res<LevelRules>,
+    outcome: Res<LevelOutcome>,
+) {
+    for PlayerMoveEvent { direction, grab } in listener.iter() {
+        let move_dir = *direction;
+
+        if *outcome != LevelOutcome::Playing || move_count.out_of_moves(&rules) {
+            continue;
+        }
+
+        // The level may still be loading
+        let player_entity = match active_character.get_single() {
+            Ok(entity) => entity,
+            Err(_) => continue,
+        };
+
+        let player_pos = *grid_objects
+            .get_component::<GridPosition>(player_entity)
+            .expect("Active character has no GridPosition");
+
+        let character = character_indices.get(player_entity).map_or(0, |index| index.0);
+
+        // Kept for undo if the move goes ahead
+        let turn = undo::Turn::new(
+            grid_objects.iter().map(|(object, position, entity)| {
+                (object, position, character_indices.get(entity).ok())
+            }),
+            character,
+            &move_count,
+        );
+
+        let new_player_pos = player_pos.offset(move_dir);
+
+        // Every block in the row in front of the player, nearest first
+        let mut pushed_blocks = Vec::new();
+        let mut next_position = new_player_pos;
+        let mut chain_is_movable = true;
+        while let Some((object, _, entity)) = grid_objects.iter().find(|(object, position, _)| {
+            **position == next_position && matches!(object, GridObject::PushBlock { .. })
+        }) {
+            chain_is_movable &= object.is_movable(&rules);
+            pushed_blocks.push((entity, next_position.offset(move_dir)));
+            next_position = next_position.offset(move_dir);
+        }
+
+        if !pushed_blocks.is_empty() {
+            if !chain_is_movable
+                || pushed_blocks.len() > rules.max_push_chain as usize
+                || move_count.out_of_pushes(&rules)
+            {
+                continue;
+            }
+
+            // Blocks in the row are all about to vacate their cells, so only the floor underneath them matters
+            let remaining_objects = || {
+                grid_objects
+                    .iter()
+                    .filter(|(_, _, entity)| !pushed_blocks.iter().any(|(block, _)| block == entity))
+                    .map(|(object, position, _)| (object, position))
+            };
+
+            let blocks_can_move = pushed_blocks.iter().all(|(_, new_block_position)| {
+                is_passable(remaining_objects(), *new_block_position, Mover::Block, move_dir)
+            });
+
+            let player_can_follow =
+                is_passable(remaining_objects(), new_player_pos, Mover::Player, move_dir);
+
+            if !blocks_can_move || !player_can_follow {
+                // Shoving the row into a breakable wall wears it down, which takes a turn
+                let front_block_target = pushed_blocks.last().map(|(_, target)| *target);
+                let hit_walls: Vec<Entity> = grid_objects
+                    .iter()
+                    .filter(|(object, position, _)| {
+                        Some(**position) == front_block_target
+                            && matches!(object, GridObject::BreakableWall { .. })
+                    })
+                    .map(|(_, _, entity)| entity)
+                    .collect();
+
+                if hit_walls.is_empty() {
+                    continue;
+                }
+
+                for wall in hit_walls {
+                    wall_hit_events.send(WallHitEvent(wall));
+                }
+
+                move_count.moves += 1;
+                history.0.push(turn);
+                player_moved_events.send(PlayerMovedEvent {
+                    character,
+                    direction: move_dir,
+                    grab: *grab,
+                    from: None,
+                });
+                continue;
+            }
+
+            move_count.pushes += 1;
+
+            for (block_entity, new_block_position) in pushed_blocks {
+                let (_, mut position, _) = grid_objects
+                    .get_mut(block_entity)
+                    .expect("Block entity not found whilst pushing");
+                *position = new_block_position;
+
+                block_move_events.send(BlockMoveEvent {
+                    block: block_entity,
+                    position: (new_block_position.x, new_block_position.y),
+                    pushed: true,
+                });
+            }
+        } else {
+            if !is_passable(
+                grid_objects.iter().map(|(object, position, _)| (object, position)),
+                new_player_pos,
+                Mover::Player,
+                move_dir,
+            ) {
+                continue;
+            }
+
+            let behind_player = player_pos.offset((-move_dir.0, -move_dir.1));
+
+            let pulled_block = grid_objects
+                .iter()
+                .find(|(object, position, _)| {
+                    *grab
+                        && !move_count.out_of_pushes(&rules)
+                        && **position == behind_player
+                        && matches!(object, GridObject::PushBlock { pullable: true, .. })
+                        && object.is_movable(&rules)
+                })
+                .map(|(_, _, entity)| entity);
+
+            if let Some(block_entity) = pulled_block {
+                // The player is about to vacate the cell the block is pulled into, which
+                // mustn't crumble away beneath it
+                let block_can_follow = !crumbles_when_left(
+                    grid_objects.iter().map(|(object, position, _)| (object, position)),
+                    player_pos,
+                ) && is_passable(
+                    grid_objects
+                        .iter()
+                        .filter(|(_, _, entity)| *entity != player_entity)
+                        .map(|(object, position, _)| (object, position)),
+                    player_pos,
+                    Mover::Block,
+                    move_dir,
+                );
+
+                if block_can_follow {
+                    move_count.pushes += 1;
+
+                    let (_, mut position, _) = grid_objects
+                        .get_mut(block_entity)
+                        .expect("Block entity not found whilst pulling");
+                    *position = player_pos;
+
+                    block_move_events.send(BlockMoveEvent {
+                        block: block_entity,
+                        position: (player_pos.x, player_pos.y),
+                        pushed: true,
                     });
                 }
             }
-        });
+        }
+
+        character_move_events.send(CharacterMoveEvent { from: player_pos });
+
+        let (_, mut position, _) = grid_objects
+            .get_mut(player_entity)
+            .expect("Player entity not found");
+        *position = new_player_pos;
+
+        move_count.moves += 1;
+        history.0.push(turn);
+        player_moved_events.send(PlayerMovedEvent {
+            character,
+            direction: move_dir,
+            grab: *grab,
+            from: Some(player_pos),
+        });
+    }
+}
+
+fn advance_conveyors(
+    mut player_moved_events: EventReader<PlayerMovedEvent>,
+    mut grid_objects: Query<(&GridObject, &mut GridPosition, Entity)>,
+    mut block_move_events: EventWriter<BlockMoveEvent>,
+    mut character_move_events: EventWriter<CharacterMoveEvent>,
+    rules: Res<LevelRules>,
+) {
+    for PlayerMovedEvent { from, .. } in player_moved_events.iter() {
+        let conveyors: Vec<(GridPosition, Direction)> = grid_objects
+            .iter()
+            .filter_map(|(object, position, _)| match object {
+                GridObject::Conveyor(direction) => Some((*position, *direction)),
+                _ => None,
+            })
+            .collect();
+
+        let mut riders: Vec<(Entity, Mover, Direction)> = grid_objects
+            .iter()
+            .filter_map(|(object, position, entity)| {
+                let mover = match object {
+                    GridObject::Player => Mover::Player,
+                    GridObject::PushBlock { .. } if object.is_movable(&rules) => Mover::Block,
+                    _ => return None,
+                };
+
+                conveyors
+                    .iter()
+                    .find(|(conveyor_position, _)| conveyor_position == position)
+                    .map(|(_, direction)| (entity, mover, *direction))
+            })
+            .collect();
+
+        // Riders furthest along their belt go first so a row of them moves together,
+        // with ties broken by position so the result never depends on query order
+        riders.sort_by_key(|(entity, _, direction)| {
+            let position = *grid_objects
+                .get_component::<GridPosition>(*entity)
+                .expect("Conveyor rider has no GridPosition");
+            let (x, y) = direction.offset();
+
+            (-(position.x * x + position.y * y), position.x, position.y)
+        });
+
+        // Floors characters left this turn crumble before anything can follow them
+        let mut crumbling = Vec::new();
+        if let Some(from) = from {
+            if crumbles_when_left(
+                grid_objects.iter().map(|(object, position, _)| (object, position)),
+                *from,
+            ) {
+                crumbling.push(*from);
+            }
+        }
+
+        for (entity, mover, direction) in riders {
+            let position = *grid_objects
+                .get_component::<GridPosition>(entity)
+                .expect("Conveyor rider has no GridPosition");
+            let new_position = position.offset(direction.offset());
+
+            let can_move = !crumbling.contains(&new_position)
+                && is_passable(
+                    grid_objects.iter().map(|(object, position, _)| (object, position)),
+                    new_position,
+                    mover,
+                    direction.offset(),
+                );
+
+            if !can_move {
+                continue;
+            }
+
+            if mover == Mover::Player {
+                if crumbles_when_left(
+                    grid_objects.iter().map(|(object, position, _)| (object, position)),
+                    position,
+                ) {
+                    crumbling.push(position);
+                }
+
+                character_move_events.send(CharacterMoveEvent { from: position });
+            }
+
+            let (_, mut rider_position, _) = grid_objects
+                .get_mut(entity)
+                .expect("Conveyor rider not found");
+            *rider_position = new_position;
+
+            if mover == Mover::Block {
+                block_move_events.send(BlockMoveEvent {
+                    block: entity,
+                    position: (new_position.x, new_position.y),
+                    pushed: false,
+                });
+            }
+        }
+    }
+}
+
+fn move_enemies(
+    mut player_moved_events: EventReader<PlayerMovedEvent>,
+    mut grid_objects: Query<(&mut GridObject, &mut GridPosition, Entity)>,
+    mut level_failed_events: EventWriter<LevelFailedEvent>,
+    mut outcome: ResMut<LevelOutcome>,
+) {
+    for _ in player_moved_events.iter() {
+        // Enemies take their turns in order of position so the result never depends on query order
+        let mut enemies: Vec<(GridPosition, Entity)> = grid_objects
+            .iter()
+            .filter(|(object, _, _)| matches!(object, GridObject::Enemy { .. }))
+            .map(|(_, position, entity)| (*position, entity))
+            .collect();
+        enemies.sort_by_key(|(position, _)| (position.x, position.y));
+
+        for (position, entity) in enemies {
+            let direction = match grid_objects.get_component::<GridObject>(entity) {
+                Ok(GridObject::Enemy { path, step }) if !path.is_empty() => {
+                    path[*step % path.len()]
+                }
+                _ => continue,
+            };
+            let new_position = position.offset(direction.offset());
+
+            let catches_character = grid_objects.iter().any(|(object, other_position, _)| {
+                *other_position == new_position && matches!(object, GridObject::Player)
+            });
+
+            let can_move = catches_character
+                || is_passable(
+                    grid_objects.iter().map(|(object, position, _)| (object, position)),
+                    new_position,
+                    Mover::Enemy,
+                    direction.offset(),
+                );
+
+            if !can_move {
+                continue;
+            }
+
+            let (mut object, mut enemy_position, _) = grid_objects
+                .get_mut(entity)
+                .expect("Enemy entity not found");
+            *enemy_position = new_position;
+
+            if let GridObject::Enemy { step, .. } = object.as_mut() {
+                *step += 1;
+            }
+
+            if catches_character && *outcome == LevelOutcome::Playing {
+                *outcome = LevelOutcome::Failed;
+                level_failed_events.send(LevelFailedEvent::Caught);
+            }
+        }
+    }
+}
+
+fn restart_level(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    level_objects: Query<Entity, With<GridPosition>>,
+    mut current_level: ResMut<CurrentLevel>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::R) {
+        return;
+    }
+
+    for entity in level_objects.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    // The level is respawned from its asset as if it had just loaded
+    current_level.spawned = false;
+}
+
+/// Recounts the level's buttons whenever a grid object is spawned or changes.
+fn update_level_counts(
+    changed_objects: Query<(), Changed<GridObject>>,
+    grid_objects: Query<&GridObject>,
+    mut level: ResMut<Level>,
+) {
+    if changed_objects.is_empty() {
+        return;
+    }
+
+    let counted = Level::count(grid_objects.iter());
+    if *level != counted {
+        *level = counted;
+    }
+}
+
+fn update_pressure_plates(
+    mut player_moved_events: EventReader<PlayerMovedEvent>,
+    mut button_state_change_event: EventWriter<ButtonStateChangeEvent>,
+    mut grid_objects: Query<(&mut GridObject, &GridPosition, Entity)>,
+) {
+    for _ in player_moved_events.iter() {
+        let occupied: Vec<GridPosition> = grid_objects
+            .iter()
+            .filter(|(object, _, _)| {
+                matches!(object, GridObject::Player | GridObject::PushBlock { .. })
+            })
+            .map(|(_, position, _)| *position)
+            .collect();
+
+        for (mut object, position, entity) in grid_objects.iter_mut() {
+            if let GridObject::PressurePlate {
+                hold_moves,
+                pressed,
+                release_moves_left,
+            } = object.as_mut()
+            {
+                if occupied.contains(position) {
+                    *release_moves_left = *hold_moves;
+
+                    if !*pressed {
+                        *pressed = true;
+                        button_state_change_event.send(ButtonStateChangeEvent::Pressed(entity));
+                    }
+                } else if *pressed {
+                    if *release_moves_left == 0 {
+                        *pressed = false;
+                        button_state_change_event.send(ButtonStateChangeEvent::Unpressed(entity));
+                    } else {
+                        *release_moves_left -= 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Wears down walls that blocks were pushed into and floors that characters left, once the
+/// player's move and the conveyors have both resolved.
+fn damage_tiles(
+    mut commands: Commands,
+    mut wall_hit_events: EventReader<WallHitEvent>,
+    mut character_move_events: EventReader<CharacterMoveEvent>,
+    mut tiles: Query<(&mut GridObject, &GridPosition, &Handle<ColorMaterial>, Entity)>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let left_floors: Vec<GridPosition> =
+        character_move_events.iter().map(|event| event.from).collect();
+    let damaged_tiles: Vec<Entity> = wall_hit_events
+        .iter()
+        .map(|WallHitEvent(wall)| *wall)
+        .chain(
+            tiles
+                .iter()
+                .filter(|(object, position, _, _)| {
+                    matches!(object, GridObject::CrumblingFloor { .. })
+                        && left_floors.contains(position)
+                })
+                .map(|(_, _, _, entity)| entity),
+        )
+        .collect();
+
+    for tile in damaged_tiles {
+        let (mut object, _, material, _) = match tiles.get_mut(tile) {
+            Ok(tile) => tile,
+            Err(_) => continue,
+        };
+
+        match object.as_mut() {
+            GridObject::BreakableWall { durability } => {
+                *durability = durability.saturating_sub(1);
+
+                if *durability == 0 {
+                    commands.entity(tile).despawn_recursive();
+                }
+            }
+            GridObject::CrumblingFloor { durability } => {
+                *durability = durability.saturating_sub(1);
 
-    level_initialized_event.send(LevelInitialized);
-}
+                if *durability == 0 {
+                    *object = GridObject::Pit;
 
-fn on_exit(mut commands: Commands, query: Query<Entity, With<Cleanup>>) {
-    for e in query.iter() {
-        commands.entity(e).despawn_recursive();
+                    if let Some(material) = materials.get_mut(material) {
+                        material.color = PIT_COLOR;
+                    }
+                }
+            }
+            _ => {}
+        }
     }
 }
 
-fn player_move_event_listener(
-    mut listener: EventReader<PlayerMoveEvent>,
-    mut grid_objects: Query<(&GridObject, &mut GridPosition, Entity)>,
-    mut block_move_events: EventWriter<BlockMoveEvent>,
+fn paint_blocks(
+    mut block_move_events: EventReader<BlockMoveEvent>,
+    mut grid_objects: Query<(&mut GridObject, &GridPosition, Option<&Children>)>,
+    mut labels: Query<(&mut Sprite, &Children), With<ColorLabel>>,
+    mut symbols: Query<&mut Text, With<ColorSymbol>>,
+    palette: Res<Palette>,
 ) {
-    for move_dir in listener.iter() {
-        let (player_entity, player_pos) = grid_objects
-            .iter()
-            .find(|e| matches!(e.0, GridObject::Player))
-            .map(|e| (e.2, *e.1))
-            .expect("No player found with GridPosition");
+    for BlockMoveEvent { block, position, .. } in block_move_events.iter() {
+        let paint = grid_objects.iter().find_map(|(object, tile_position, _)| match object {
+            GridObject::Paint(paint) if (tile_position.x, tile_position.y) == *position => {
+                Some(paint.clone())
+            }
+            _ => None,
+        });
 
-        let new_player_pos = GridPosition {
-            x: player_pos.x + move_dir.0,
-            y: player_pos.y + move_dir.1,
+        let paint = match paint {
+            Some(paint) => paint,
+            None => continue,
         };
 
-        // If there's a wall in the way, don't move
-        if let Some((obj, _, other_entity)) = grid_objects.iter().find(|(object, position, _)| {
-            position.x == new_player_pos.x
-                && position.y == new_player_pos.y
-                && !matches!(object, GridObject::Button(_, _))
-        }) {
-            match obj {
-                GridObject::Player => return,
-                GridObject::PushBlock { .. } => {
-                    let new_block_position = GridPosition {
-                        x: new_player_pos.x + move_dir.0,
-                        y: new_player_pos.y + move_dir.1,
-                    };
-
-                    if let None = grid_objects.iter().find(|(object, position, _)| {
-                        let is_overlapped = position.x == new_block_position.x
-                            && position.y == new_block_position.y;
+        if let Ok((mut grid_object, _, children)) = grid_objects.get_mut(*block) {
+            if let GridObject::PushBlock { kind, .. } = grid_object.as_mut() {
+                // Only blocks which have been discovered have a label showing their colour
+                for child in children.into_iter().flat_map(|children| children.iter()) {
+                    if let Ok((mut sprite, label_children)) = labels.get_mut(*child) {
+                        sprite.color = palette.color(&paint);
 
-                        let is_button = matches!(object, GridObject::Button(_, _));
-
-                        if is_button {
-                            return false;
+                        for label_child in label_children.iter() {
+                            if let Ok(mut text) = symbols.get_mut(*label_child) {
+                                text.sections[0].value = palette.symbol(&paint).to_string();
+                            }
                         }
-
-                        return is_overlapped;
-                    }) {
-                        let (_, mut position, _) = grid_objects
-                            .get_mut(player_entity)
-                            .expect("Player entity not found whilst pushing");
-                        position.x = new_player_pos.x;
-                        position.y = new_player_pos.y;
-
-                        let (_, mut position, _) = grid_objects
-                            .get_mut(other_entity)
-                            .expect("Block entity not found whilst pushing");
-                        position.x = new_block_position.x;
-                        position.y = new_block_position.y;
-
-                        block_move_events.send(BlockMoveEvent {
-                            block: other_entity,
-                            position: (new_block_position.x, new_block_position.y),
-                        });
                     }
-
-                    return;
-                }
-                GridObject::Button(_, _) => {
-                    let (_, mut position, _) = grid_objects
-                        .get_mut(player_entity)
-                        .expect("Player entity not found");
-                    position.x = new_player_pos.x;
-                    position.y = new_player_pos.y;
                 }
-                GridObject::Wall => return,
+
+                *kind = paint;
             }
-        } else {
-            let (_, mut position, _) = grid_objects
-                .get_mut(player_entity)
-                .expect("Player entity not found whilst pushing");
-            position.x = new_player_pos.x;
-            position.y = new_player_pos.y;
         }
     }
 }
@@ -271,53 +1239,156 @@ fn player_move_event_listener(
 fn decrease_pushes_remaining(
     mut block_move_events: EventReader<BlockMoveEvent>,
     mut query: Query<&mut GridObject>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>
 ) {
-    for BlockMoveEvent { block, .. } in block_move_events.iter() {
+    for BlockMoveEvent { block, pushed, .. } in block_move_events.iter() {
+        if !pushed {
+            continue;
+        }
+
         if let Ok(mut grid_object) = query.get_mut(*block) {
             if let GridObject::PushBlock {
                 pushes_left: pushes_remaining,
-                kind
+                times_pushed,
+                ..
             } = grid_object.as_mut()
             {
+                *times_pushed += 1;
+
                 if *pushes_remaining == 0 { continue; }
                 *pushes_remaining -= 1;
                 println!("Pushes left {}", pushes_remaining);
+            }
+        }
+    }
+}
 
-                if *pushes_remaining == 0 {
-                    commands.entity(*block)
-                        .with_children(|parent| {
-                            parent.spawn_bundle(SpriteBundle {
-                                texture: asset_server.load("sprites/color_label.png"),
-                                sprite: Sprite {
-                                    custom_size: Some(Vec2::splat(32.0)),
-                                    color: match *kind {
-                                        BlockType::Red => Color::ORANGE_RED,
-                                        BlockType::Green => Color::SEA_GREEN,
-                                        BlockType::Blue => Color::ALICE_BLUE,
-                                    },
-                                    ..Default::default()
-                                },
-                                ..Default::default()
-                            });
-                        });
-                }
+fn discover_blocks(
+    mut commands: Commands,
+    mut block_move_events: EventReader<BlockMoveEvent>,
+    mut block_discovered_events: EventWriter<BlockDiscoveredEvent>,
+    mut grid_objects: Query<(&mut GridObject, &GridPosition, Entity)>,
+    rules: Res<LevelRules>,
+    palette: Res<Palette>,
+    asset_server: Res<AssetServer>,
+) {
+    for BlockMoveEvent { block, position, .. } in block_move_events.iter() {
+        let (kind, is_discovered_now) = match grid_objects.get_component::<GridObject>(*block) {
+            Ok(GridObject::PushBlock {
+                kind,
+                pushes_left,
+                discovery,
+                times_pushed,
+                discovered: false,
+                ..
+            }) => {
+                let is_discovered_now = match discovery.unwrap_or(rules.discovery) {
+                    Discovery::OutOfPushes => *pushes_left == 0,
+                    Discovery::AfterPushes(pushes) => *times_pushed >= pushes,
+                    Discovery::NextToButton => {
+                        grid_objects.iter().any(|(object, button_position, _)| {
+                            let distance = (button_position.x - position.0).abs()
+                                + (button_position.y - position.1).abs();
+
+                            distance == 1
+                                && matches!(object, GridObject::Button(button_kind, _) if button_kind == kind)
+                        })
+                    }
+                    Discovery::Known => true,
+                };
+
+                (kind.clone(), is_discovered_now)
+            }
+            _ => continue,
+        };
+
+        if !is_discovered_now {
+            continue;
+        }
+
+        let discovered_blocks: Vec<Entity> = if rules.reveal_matching {
+            grid_objects
+                .iter()
+                .filter(|(object, _, _)| match object {
+                    GridObject::PushBlock {
+                        kind: other_kind,
+                        discovered,
+                        ..
+                    } => !discovered && *other_kind == kind,
+                    _ => false,
+                })
+                .map(|(_, _, entity)| entity)
+                .collect()
+        } else {
+            vec![*block]
+        };
+
+        for entity in discovered_blocks {
+            let (mut object, block_position, _) = grid_objects
+                .get_mut(entity)
+                .expect("Block entity not found whilst discovering");
+
+            if let GridObject::PushBlock { discovered, .. } = object.as_mut() {
+                *discovered = true;
             }
+
+            commands.entity(entity).with_children(|parent| {
+                spawn_color_label(parent, &palette, &kind, &asset_server);
+            });
+
+            block_discovered_events.send(BlockDiscoveredEvent {
+                block: entity,
+                position: (block_position.x, block_position.y),
+            });
         }
     }
 }
 
+fn spawn_color_label(
+    parent: &mut ChildBuilder,
+    palette: &Palette,
+    kind: &BlockType,
+    asset_server: &AssetServer,
+) {
+    parent
+        .spawn_bundle(SpriteBundle {
+            texture: asset_server.load("sprites/color_label.png"),
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(32.0)),
+                color: palette.color(kind),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(ColorLabel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(color_symbol_bundle(
+                    palette,
+                    kind,
+                    asset_server.load("font/roboto_thin.ttf"),
+                    Transform::from_translation(Vec3::new(0.0, 0.0, 1.0)),
+                ))
+                .insert(ColorSymbol);
+        });
+}
+
 fn block_move_event_listener(
     mut move_events: EventReader<BlockMoveEvent>,
+    mut discovered_events: EventReader<BlockDiscoveredEvent>,
     mut button_state_change_event: EventWriter<ButtonStateChangeEvent>,
     mut query: Query<(&GridPosition, &mut GridObject, Entity)>,
 ) {
-    for BlockMoveEvent { block, position: moved_block_position } in move_events.iter() {
-        let block_object = query.get_component_mut::<GridObject>(*block).unwrap();
+    // Blocks discovered where they stand might already be sitting on their button
+    let changed_blocks = move_events
+        .iter()
+        .map(|event| (event.block, event.position))
+        .chain(discovered_events.iter().map(|event| (event.block, event.position)));
+
+    for (block, moved_block_position) in changed_blocks {
+        let block_object = query.get_component_mut::<GridObject>(block).unwrap();
         let (block_kind, is_block_discovered) =
-            if let GridObject::PushBlock { kind, pushes_left } = block_object.as_ref() {
-                (*kind, *pushes_left == 0)
+            if let GridObject::PushBlock { kind, discovered, .. } = block_object.as_ref() {
+                (kind.clone(), *discovered)
             } else {
                 continue;
             };
@@ -329,12 +1400,11 @@ fn block_move_event_listener(
                 
                 // This is a button, we care about this one
                 // the moved block is already on the button so we know it's being removed
-                if let Some(current_block) = pressing_entity_option.take() {
-                    if current_block == *block {
-                        // TODO WT: Events for buttons being pressed and unpressed (to change the state of the button sprite).
-                        println!("Block moved off of button");
-                        button_state_change_event.send(ButtonStateChangeEvent::Unpressed(button_entity));
-                    }
+                if *pressing_entity_option == Some(block) {
+                    *pressing_entity_option = None;
+                    // TODO WT: Events for buttons being pressed and unpressed (to change the state of the button sprite).
+                    println!("Block moved off of button");
+                    button_state_change_event.send(ButtonStateChangeEvent::Unpressed(button_entity));
                 }
 
                 if button_pos.x != moved_block_position.0 || button_pos.y != moved_block_position.1 {
@@ -356,7 +1426,7 @@ fn block_move_event_listener(
                     &block_kind, &button_kind
                 );
 
-                *pressing_entity_option = Some(*block);
+                *pressing_entity_option = Some(block);
 
                 println!("Block moved onto button");
                 button_state_change_event.send(ButtonStateChangeEvent::Pressed(entity));
@@ -373,7 +1443,7 @@ fn update_player_keyboard(
     keyboard_input: Res<Input<KeyCode>>,
     mut writer: EventWriter<PlayerMoveEvent>,
 ) {
-    let mut move_dir = PlayerMoveEvent(0, 0);
+    let mut move_dir = (0, 0);
 
     if keyboard_input.just_pressed(KeyCode::W) || keyboard_input.just_pressed(KeyCode::Up) {
         move_dir.1 += 1;
@@ -390,7 +1460,100 @@ fn update_player_keyboard(
         return;
     }
 
-    writer.send(move_dir);
+    let grab = keyboard_input.pressed(KeyCode::LShift) || keyboard_input.pressed(KeyCode::Space);
+
+    writer.send(PlayerMoveEvent {
+        direction: move_dir,
+        grab,
+    });
+}
+
+fn swap_active_character(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    characters: Query<(
+        Entity,
+        &CharacterIndex,
+        &Handle<ColorMaterial>,
+        Option<&ActiveCharacter>,
+    )>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Tab) {
+        return;
+    }
+
+    // Entity ids are reused after a restart, so they can't be relied on for the order
+    let mut characters: Vec<_> = characters.iter().collect();
+    characters.sort_by_key(|(_, index, _, _)| **index);
+
+    let active_index = match characters.iter().position(|(_, _, _, active)| active.is_some()) {
+        Some(index) => index,
+        None => return,
+    };
+    let next_index = (active_index + 1) % characters.len();
+
+    let (previous, _, previous_material, _) = characters[active_index];
+    let (next, _, next_material, _) = characters[next_index];
+
+    commands.entity(previous).remove::<ActiveCharacter>();
+    commands.entity(next).insert(ActiveCharacter);
+
+    if let Some(material) = materials.get_mut(previous_material) {
+        material.color = INACTIVE_CHARACTER_COLOR;
+    }
+    if let Some(material) = materials.get_mut(next_material) {
+        material.color = ACTIVE_CHARACTER_COLOR;
+    }
+}
+
+/// Hands control to the character with index `character`, taking effect from the next stage.
+fn set_active_character(
+    commands: &mut Commands,
+    characters: &Query<(
+        Entity,
+        &CharacterIndex,
+        &Handle<ColorMaterial>,
+        Option<&ActiveCharacter>,
+    )>,
+    materials: &mut Assets<ColorMaterial>,
+    character: usize,
+) {
+    for (entity, index, material, active) in characters.iter() {
+        let color = if index.0 == character {
+            commands.entity(entity).insert(ActiveCharacter);
+            ACTIVE_CHARACTER_COLOR
+        } else if active.is_some() {
+            commands.entity(entity).remove::<ActiveCharacter>();
+            INACTIVE_CHARACTER_COLOR
+        } else {
+            continue;
+        };
+
+        if let Some(material) = materials.get_mut(material) {
+            material.color = color;
+        }
+    }
+}
+
+fn toggle_colorblind_mode(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut colorblind_mode: ResMut<ColorblindMode>,
+) {
+    if keyboard_input.just_pressed(KeyCode::C) {
+        colorblind_mode.0 = !colorblind_mode.0;
+    }
+}
+
+fn apply_colorblind_mode(
+    colorblind_mode: Res<ColorblindMode>,
+    mut symbols: Query<&mut Visibility, With<ColorSymbol>>,
+) {
+    for mut visibility in symbols.iter_mut() {
+        if visibility.is_visible != colorblind_mode.0 {
+            visibility.is_visible = colorblind_mode.0;
+        }
+    }
 }
 
 fn apply_grid_entity_position(mut query: Query<(&GridPosition, &mut Transform)>, grid: Res<Grid>) {
@@ -401,79 +1564,134 @@ fn apply_grid_entity_position(mut query: Query<(&GridPosition, &mut Transform)>,
 }
 
 impl LevelData {
+    /// Reads a level file of any version, upgrading it to the latest.
+    pub fn from_ron(ron: &str) -> anyhow::Result<Self> {
+        let mut level: Self = ron::de::from_str(&migration::migrate(ron)?)?;
+        level.version = migration::LATEST_VERSION;
+        Ok(level)
+    }
+
+    pub fn to_ron(&self) -> anyhow::Result<String> {
+        Ok(ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?)
+    }
+
+    pub fn info(&self) -> &LevelInfo {
+        &self.info
+    }
+
+    /// Spawns the level with the `active_character`th character controlled. State that only
+    /// exists in play, such as how far along an enemy is, is kept so undo can respawn a level
+    /// mid-game.
     fn spawn(
         &self,
         commands: &mut Commands,
         meshes: &mut ResMut<Assets<Mesh>>,
         materials: &mut ResMut<Assets<ColorMaterial>>,
         asset_server: &Res<AssetServer>,
+        active_character: usize,
     ) {
+        let mut character_count = 0;
+        let mut buttons = Vec::new();
+        let mut discovered_blocks = Vec::new();
+
         for (object_type, position) in &self.objects {
             match object_type {
                 GridObject::Player => {
-                    commands
-                        .spawn_bundle(ColorMesh2dBundle {
-                            mesh: meshes.add(shape::Quad::default().into()).into(),
-                            material: materials.add(Color::ORANGE.into()),
-                            transform: Transform::from_scale(Vec3::splat(60.0))
-                                .with_translation(Vec3::new(0.0, 0.0, 100.0)),
-                            ..Default::default()
-                        })
+                    let is_active = character_count == active_character;
+                    let index = CharacterIndex(character_count);
+                    character_count += 1;
+
+                    let mut character = commands.spawn_bundle(ColorMesh2dBundle {
+                        mesh: meshes.add(shape::Quad::default().into()).into(),
+                        material: materials.add(
+                            if is_active {
+                                ACTIVE_CHARACTER_COLOR
+                            } else {
+                                INACTIVE_CHARACTER_COLOR
+                            }
+                            .into(),
+                        ),
+                        transform: Transform::from_scale(Vec3::splat(60.0))
+                            .with_translation(Vec3::new(0.0, 0.0, 100.0)),
+                        ..Default::default()
+                    });
+
+                    character
                         .insert(Cleanup)
                         .insert(GridObject::Player)
+                        .insert(index)
                         .insert(*position);
+
+                    if is_active {
+                        character.insert(ActiveCharacter);
+                    }
                 }
-                GridObject::PushBlock { kind, pushes_left } => {
-                    commands
-                        .spawn_bundle(SpriteBundle {
-                            texture: asset_server.load("sprites/box.png"),
-                            sprite: Sprite {
-                                custom_size: Some(Vec2::splat(64.0)),
-                                ..Default::default()
-                            },
-                            transform: Transform::from_translation(Vec3::new(0.0, 0.0, 99.0)),
+                GridObject::PushBlock {
+                    kind,
+                    pushes_left,
+                    pullable,
+                    discovery,
+                    times_pushed,
+                    discovered,
+                } => {
+                    let discovered = *discovered
+                        || discovery.unwrap_or(self.rules.discovery) == Discovery::Known;
+
+                    let mut block = commands.spawn_bundle(SpriteBundle {
+                        texture: asset_server.load("sprites/box.png"),
+                        sprite: Sprite {
+                            custom_size: Some(Vec2::splat(64.0)),
+                            // Tint pullable blocks so they stand out without giving away their colour
+                            color: if *pullable { Color::SILVER } else { Color::WHITE },
                             ..Default::default()
-                        })
+                        },
+                        transform: Transform::from_translation(Vec3::new(0.0, 0.0, 99.0)),
+                        ..Default::default()
+                    });
+
+                    block
                         .insert(Cleanup)
                         .insert(GridObject::PushBlock {
-                            kind: *kind,
+                            kind: kind.clone(),
                             pushes_left: *pushes_left,
+                            pullable: *pullable,
+                            discovery: *discovery,
+                            times_pushed: *times_pushed,
+                            discovered,
                         })
-                        .insert(*position)
-                        .with_children(|parent| {
-                            // parent.spawn_bundle(SpriteBundle {
-                            //     texture: asset_server.load("sprites/color_label.png"),
-                            //     sprite: Sprite {
-                            //         custom_size: Some(Vec2::splat(32.0)),
-                            //         color: match kind {
-                            //             BlockType::Red => Color::ORANGE_RED,
-                            //             BlockType::Green => Color::SEA_GREEN,
-                            //             BlockType::Blue => Color::ALICE_BLUE,
-                            //         },
-                            //         ..Default::default()
-                            //     },
-                            //     ..Default::default()
-                            // });
+                        .insert(*position);
+
+                    if discovered {
+                        discovered_blocks.push((block.id(), *position, kind.clone()));
+                        block.with_children(|parent| {
+                            spawn_color_label(parent, &self.palette, kind, asset_server);
                         });
+                    }
                 }
                 GridObject::Button(kind, _) => {
-                    commands
+                    let button = commands
                         .spawn_bundle(ColorMesh2dBundle {
                             mesh: meshes.add(shape::Quad::default().into()).into(),
-                            material: materials.add(
-                                match kind {
-                                    BlockType::Red => Color::ORANGE_RED,
-                                    BlockType::Green => Color::SEA_GREEN,
-                                    BlockType::Blue => Color::ALICE_BLUE,
-                                }
-                                .into(),
-                            ),
+                            material: materials.add(self.palette.color(kind).into()),
                             transform: Transform::from_scale(Vec3::splat(64.0))
                                 .with_translation(Vec3::new(0.0, 0.0, 5.0)),
                             ..Default::default()
                         })
                         .insert(Cleanup)
-                        .insert(GridObject::Button(*kind, None))
+                        .insert(GridObject::Button(kind.clone(), None))
+                        .insert(*position)
+                        .id();
+                    buttons.push((button, *position, kind.clone()));
+
+                    commands
+                        .spawn_bundle(color_symbol_bundle(
+                            &self.palette,
+                            kind,
+                            asset_server.load("font/roboto_thin.ttf"),
+                            Transform::from_translation(Vec3::new(0.0, 0.0, 6.0)),
+                        ))
+                        .insert(Cleanup)
+                        .insert(ColorSymbol)
                         .insert(*position);
                 }
                 GridObject::Wall => {
@@ -489,7 +1707,232 @@ impl LevelData {
                         .insert(GridObject::Wall)
                         .insert(*position);
                 }
+                GridObject::OneWay {
+                    direction,
+                    player,
+                    blocks,
+                } => {
+                    let color = match (*player, *blocks) {
+                        (true, false) => Color::ORANGE,
+                        (false, true) => Color::BEIGE,
+                        _ => Color::WHITE,
+                    };
+
+                    commands
+                        .spawn_bundle(ColorMesh2dBundle {
+                            mesh: meshes.add(arrow_mesh()).into(),
+                            material: materials.add(color.into()),
+                            transform: Transform::from_scale(Vec3::splat(40.0))
+                                .with_rotation(direction.rotation())
+                                .with_translation(Vec3::new(0.0, 0.0, 5.0)),
+                            ..Default::default()
+                        })
+                        .insert(Cleanup)
+                        .insert(GridObject::OneWay {
+                            direction: *direction,
+                            player: *player,
+                            blocks: *blocks,
+                        })
+                        .insert(*position);
+                }
+                GridObject::BreakableWall { durability } => {
+                    commands
+                        .spawn_bundle(ColorMesh2dBundle {
+                            mesh: meshes.add(shape::Quad::default().into()).into(),
+                            material: materials.add(Color::rgb(0.45, 0.35, 0.3).into()),
+                            transform: Transform::from_scale(Vec3::splat(60.0))
+                                .with_translation(Vec3::new(0.0, 0.0, 99.0)),
+                            ..Default::default()
+                        })
+                        .insert(Cleanup)
+                        .insert(GridObject::BreakableWall {
+                            durability: *durability,
+                        })
+                        .insert(*position);
+                }
+                GridObject::CrumblingFloor { durability } => {
+                    commands
+                        .spawn_bundle(ColorMesh2dBundle {
+                            mesh: meshes.add(shape::Quad::default().into()).into(),
+                            material: materials.add(Color::rgb(0.6, 0.5, 0.4).into()),
+                            transform: Transform::from_scale(Vec3::splat(60.0))
+                                .with_translation(Vec3::new(0.0, 0.0, 4.0)),
+                            ..Default::default()
+                        })
+                        .insert(Cleanup)
+                        .insert(GridObject::CrumblingFloor {
+                            durability: *durability,
+                        })
+                        .insert(*position);
+                }
+                GridObject::Pit => {
+                    commands
+                        .spawn_bundle(ColorMesh2dBundle {
+                            mesh: meshes.add(shape::Quad::default().into()).into(),
+                            material: materials.add(PIT_COLOR.into()),
+                            transform: Transform::from_scale(Vec3::splat(60.0))
+                                .with_translation(Vec3::new(0.0, 0.0, 4.0)),
+                            ..Default::default()
+                        })
+                        .insert(Cleanup)
+                        .insert(GridObject::Pit)
+                        .insert(*position);
+                }
+                GridObject::PressurePlate {
+                    hold_moves,
+                    pressed,
+                    release_moves_left,
+                } => {
+                    commands
+                        .spawn_bundle(ColorMesh2dBundle {
+                            mesh: meshes.add(shape::Quad::default().into()).into(),
+                            material: materials.add(Color::SILVER.into()),
+                            transform: Transform::from_scale(Vec3::splat(48.0))
+                                .with_translation(Vec3::new(0.0, 0.0, 5.0)),
+                            ..Default::default()
+                        })
+                        .insert(Cleanup)
+                        .insert(GridObject::PressurePlate {
+                            hold_moves: *hold_moves,
+                            pressed: *pressed,
+                            release_moves_left: *release_moves_left,
+                        })
+                        .insert(*position);
+                }
+                GridObject::Conveyor(direction) => {
+                    commands
+                        .spawn_bundle(ColorMesh2dBundle {
+                            mesh: meshes.add(arrow_mesh()).into(),
+                            material: materials.add(Color::GOLD.into()),
+                            transform: Transform::from_scale(Vec3::splat(28.0))
+                                .with_rotation(direction.rotation())
+                                .with_translation(Vec3::new(0.0, 0.0, 5.0)),
+                            ..Default::default()
+                        })
+                        .insert(Cleanup)
+                        .insert(GridObject::Conveyor(*direction))
+                        .insert(*position);
+                }
+                GridObject::Enemy { path, step } => {
+                    commands
+                        .spawn_bundle(ColorMesh2dBundle {
+                            mesh: meshes.add(shape::Quad::default().into()).into(),
+                            material: materials.add(Color::CRIMSON.into()),
+                            transform: Transform::from_scale(Vec3::splat(48.0))
+                                .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_4))
+                                .with_translation(Vec3::new(0.0, 0.0, 100.0)),
+                            ..Default::default()
+                        })
+                        .insert(Cleanup)
+                        .insert(GridObject::Enemy {
+                            path: path.clone(),
+                            step: *step,
+                        })
+                        .insert(*position);
+                }
+                GridObject::Gem { collected } => {
+                    commands
+                        .spawn_bundle(ColorMesh2dBundle {
+                            mesh: meshes.add(shape::Quad::default().into()).into(),
+                            material: materials.add(Color::CYAN.into()),
+                            transform: Transform::from_scale(Vec3::splat(20.0))
+                                .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_4))
+                                .with_translation(Vec3::new(0.0, 0.0, 6.0)),
+                            visibility: Visibility {
+                                is_visible: !collected,
+                            },
+                            ..Default::default()
+                        })
+                        .insert(Cleanup)
+                        .insert(GridObject::Gem {
+                            collected: *collected,
+                        })
+                        .insert(*position);
+                }
+                GridObject::Exit => {
+                    commands
+                        .spawn_bundle(ColorMesh2dBundle {
+                            mesh: meshes.add(shape::Quad::default().into()).into(),
+                            material: materials.add(EXIT_INACTIVE_COLOR.into()),
+                            transform: Transform::from_scale(Vec3::splat(56.0))
+                                .with_translation(Vec3::new(0.0, 0.0, 4.0)),
+                            ..Default::default()
+                        })
+                        .insert(Cleanup)
+                        .insert(GridObject::Exit)
+                        .insert(*position);
+                }
+                GridObject::Door { to } => {
+                    commands
+                        .spawn_bundle(ColorMesh2dBundle {
+                            mesh: meshes.add(shape::Quad::default().into()).into(),
+                            material: materials.add(Color::BISQUE.into()),
+                            transform: Transform::from_scale(Vec3::new(56.0, 20.0, 1.0))
+                                .with_translation(Vec3::new(0.0, 0.0, 4.0)),
+                            ..Default::default()
+                        })
+                        .insert(Cleanup)
+                        .insert(GridObject::Door { to: *to })
+                        .insert(*position);
+                }
+                GridObject::Paint(kind) => {
+                    commands
+                        .spawn_bundle(ColorMesh2dBundle {
+                            mesh: meshes.add(shape::Quad::default().into()).into(),
+                            material: materials.add(self.palette.color(kind).into()),
+                            transform: Transform::from_scale(Vec3::splat(32.0))
+                                .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_4))
+                                .with_translation(Vec3::new(0.0, 0.0, 5.0)),
+                            ..Default::default()
+                        })
+                        .insert(Cleanup)
+                        .insert(GridObject::Paint(kind.clone()))
+                        .insert(*position);
+
+                    commands
+                        .spawn_bundle(color_symbol_bundle(
+                            &self.palette,
+                            kind,
+                            asset_server.load("font/roboto_thin.ttf"),
+                            Transform::from_translation(Vec3::new(0.0, 0.0, 6.0)),
+                        ))
+                        .insert(Cleanup)
+                        .insert(ColorSymbol)
+                        .insert(*position);
+                }
+            }
+        }
+
+        // Blocks already sitting on a matching button hold it down from the start
+        for (button, position, kind) in buttons {
+            let pressing_block = discovered_blocks
+                .iter()
+                .find(|(_, block_position, block_kind)| {
+                    *block_position == position && *block_kind == kind
+                })
+                .map(|(block, _, _)| *block);
+
+            if pressing_block.is_some() {
+                commands
+                    .entity(button)
+                    .insert(GridObject::Button(kind, pressing_block));
             }
         }
     }
 }
+
+/// A unit triangle pointing up, rotated to show the direction of one way tiles.
+fn arrow_mesh() -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_indices(Some(Indices::U32(vec![0, 1, 2])));
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        vec![[-0.5, -0.5, 0.0], [0.5, -0.5, 0.0], [0.0, 0.5, 0.0]],
+    );
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; 3]);
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        vec![[0.0, 1.0], [1.0, 1.0], [0.5, 0.0]],
+    );
+    mesh
+}
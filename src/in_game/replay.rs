@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+
+use crate::app_state::AppState;
+
+use super::set_active_character;
+use super::undo::TurnUndoneEvent;
+use super::ActiveCharacter;
+use super::CharacterIndex;
+use super::Cleanup;
+use super::CurrentLevel;
+use super::GridPosition;
+use super::LevelCompleteEvent;
+use super::LevelInitialized;
+use super::LevelOutcome;
+use super::PlayerMoveEvent;
+use super::PlayerMovedEvent;
+
+/// How long each turn is shown for whilst a replay plays.
+const REPLAY_STEP_SECONDS: f32 = 0.3;
+
+/// A turn as it was taken, which is all it takes to play it again as levels are deterministic.
+#[derive(Clone, Copy)]
+struct ReplayStep {
+    character: usize,
+    direction: (i32, i32),
+    grab: bool,
+}
+
+/// Every turn taken since the level was spawned and not undone, most recent last.
+#[derive(Default)]
+struct Replay(Vec<ReplayStep>);
+
+/// A replay being played back from the start of the level.
+struct ReplayPlayback {
+    steps: Vec<ReplayStep>,
+    next: usize,
+    timer: Timer,
+}
+
+#[derive(Component)]
+struct ReplayPrompt;
+
+/// Records the turns taken in a level, and plays them back from the start once it's complete.
+pub struct ReplayPlugin;
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(AppState::InGame).with_system(on_enter))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(record_turns)
+                    .with_system(show_prompt)
+                    .with_system(start_playback)
+                    .with_system(play_back),
+            );
+    }
+}
+
+fn on_enter(mut commands: Commands) {
+    commands.insert_resource(Replay::default());
+    commands.remove_resource::<ReplayPlayback>();
+}
+
+fn record_turns(
+    mut commands: Commands,
+    mut level_initialized_events: EventReader<LevelInitialized>,
+    mut player_moved_events: EventReader<PlayerMovedEvent>,
+    mut turn_undone_events: EventReader<TurnUndoneEvent>,
+    mut replay: ResMut<Replay>,
+    prompts: Query<Entity, With<ReplayPrompt>>,
+) {
+    let mut level_changed = false;
+
+    if level_initialized_events.iter().count() > 0 {
+        replay.0.clear();
+        level_changed = true;
+    }
+
+    for _ in turn_undone_events.iter() {
+        replay.0.pop();
+        level_changed = true;
+    }
+
+    for event in player_moved_events.iter() {
+        replay.0.push(ReplayStep {
+            character: event.character,
+            direction: event.direction,
+            grab: event.grab,
+        });
+    }
+
+    if level_changed {
+        for entity in prompts.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn show_prompt(
+    mut commands: Commands,
+    mut level_complete_events: EventReader<LevelCompleteEvent>,
+    asset_server: Res<AssetServer>,
+) {
+    if level_complete_events.iter().count() == 0 {
+        return;
+    }
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "Press P to watch a replay",
+                TextStyle {
+                    font: asset_server.load("font/roboto_thin.ttf"),
+                    font_size: 25.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(Cleanup)
+        .insert(ReplayPrompt);
+}
+
+/// Respawns the level, as restarting does, and queues up the turns that completed it.
+fn start_playback(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    level_objects: Query<Entity, With<GridPosition>>,
+    mut current_level: ResMut<CurrentLevel>,
+    replay: Res<Replay>,
+    outcome: Res<LevelOutcome>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::P) || *outcome != LevelOutcome::Complete {
+        return;
+    }
+
+    for entity in level_objects.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    current_level.spawned = false;
+
+    commands.insert_resource(ReplayPlayback {
+        steps: replay.0.clone(),
+        next: 0,
+        timer: Timer::from_seconds(REPLAY_STEP_SECONDS, true),
+    });
+}
+
+fn play_back(
+    mut commands: Commands,
+    time: Res<Time>,
+    playback: Option<ResMut<ReplayPlayback>>,
+    characters: Query<(
+        Entity,
+        &CharacterIndex,
+        &Handle<ColorMaterial>,
+        Option<&ActiveCharacter>,
+    )>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut player_move_events: EventWriter<PlayerMoveEvent>,
+    outcome: Res<LevelOutcome>,
+) {
+    let mut playback = match playback {
+        Some(playback) => playback,
+        None => return,
+    };
+
+    // Waits whilst the level respawns, or whilst it isn't being played
+    if *outcome != LevelOutcome::Playing || !playback.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let step = match playback.steps.get(playback.next) {
+        Some(step) => *step,
+        None => return,
+    };
+
+    // The new character only takes control next frame, so its move waits until then
+    let is_active = characters
+        .iter()
+        .any(|(_, index, _, active)| index.0 == step.character && active.is_some());
+    if !is_active {
+        set_active_character(&mut commands, &characters, &mut materials, step.character);
+        return;
+    }
+
+    player_move_events.send(PlayerMoveEvent {
+        direction: step.direction,
+        grab: step.grab,
+    });
+    playback.next += 1;
+
+    if playback.next == playback.steps.len() {
+        commands.remove_resource::<ReplayPlayback>();
+    }
+}
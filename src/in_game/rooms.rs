@@ -0,0 +1,182 @@
+use bevy::{prelude::*, render::camera::CameraPlugin};
+use serde::{Deserialize, Serialize};
+
+use crate::app_state::AppState;
+
+use super::is_passable;
+use super::ActiveCharacter;
+use super::Cleanup;
+use super::Grid;
+use super::GridObject;
+use super::GridPosition;
+use super::Mover;
+use super::MoveSystem;
+use super::PlayerMovedEvent;
+
+/// A rectangle of the grid the camera frames whilst the active character is inside it.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Room {
+    /// The bottom left cell of the room.
+    pub origin: GridPosition,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Room {
+    pub fn contains(&self, position: &GridPosition) -> bool {
+        (self.origin.x..self.origin.x + self.width).contains(&position.x)
+            && (self.origin.y..self.origin.y + self.height).contains(&position.y)
+    }
+
+    fn center(&self, grid: &Grid) -> Vec2 {
+        Vec2::new(
+            (self.origin.x as f32 + (self.width - 1) as f32 / 2.0) * grid.cell_size,
+            (self.origin.y as f32 + (self.height - 1) as f32 / 2.0) * grid.cell_size,
+        )
+    }
+}
+
+/// The rooms a level is made of. Every room stays spawned, so blocks keep their state when
+/// the characters leave.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Rooms(pub Vec<Room>);
+
+impl Default for Rooms {
+    fn default() -> Self {
+        Self(vec![Room {
+            origin: GridPosition { x: -16, y: -16 },
+            width: 32,
+            height: 32,
+        }])
+    }
+}
+
+/// Index of the room holding the active character.
+#[derive(Default)]
+pub struct CurrentRoom(pub Option<usize>);
+
+/// Background tiles of the current room, rebuilt when the room changes.
+#[derive(Component)]
+struct RoomBackground;
+
+pub struct RoomsPlugin;
+impl Plugin for RoomsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(AppState::InGame).with_system(on_enter))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(
+                        use_doors
+                            .label(MoveSystem::Doors)
+                            .after(MoveSystem::Enemies)
+                            .before(MoveSystem::Buttons),
+                    )
+                    .with_system(follow_current_room),
+            );
+    }
+}
+
+fn on_enter(mut commands: Commands) {
+    commands.insert_resource(CurrentRoom::default());
+}
+
+/// Sends characters that end a move on a door to the other side of it.
+fn use_doors(
+    mut player_moved_events: EventReader<PlayerMovedEvent>,
+    mut grid_objects: Query<(&GridObject, &mut GridPosition)>,
+) {
+    // Characters carry on through the door in the direction they were travelling
+    let direction = match player_moved_events.iter().last() {
+        Some(PlayerMovedEvent { direction, .. }) => *direction,
+        None => return,
+    };
+
+    let doors: Vec<(GridPosition, GridPosition)> = grid_objects
+        .iter()
+        .filter_map(|(object, position)| match object {
+            GridObject::Door { to } => Some((*position, *to)),
+            _ => None,
+        })
+        .collect();
+
+    for (door, to) in doors {
+        if !is_passable(grid_objects.iter(), to, Mover::Player, direction) {
+            continue;
+        }
+
+        if let Some((_, mut position)) = grid_objects
+            .iter_mut()
+            .find(|(object, position)| matches!(object, GridObject::Player) && **position == door)
+        {
+            *position = to;
+        }
+    }
+}
+
+/// Moves the camera and background to whichever room the active character is in.
+#[allow(clippy::too_many_arguments)]
+fn follow_current_room(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    grid: Res<Grid>,
+    rooms: Res<Rooms>,
+    mut current_room: ResMut<CurrentRoom>,
+    active_character: Query<&GridPosition, With<ActiveCharacter>>,
+    mut cameras: Query<(&Camera, &mut Transform)>,
+    backgrounds: Query<Entity, With<RoomBackground>>,
+) {
+    let position = match active_character.get_single() {
+        Ok(position) => position,
+        Err(_) => return,
+    };
+
+    let index = match rooms.0.iter().position(|room| room.contains(position)) {
+        Some(index) => index,
+        None => return,
+    };
+
+    if current_room.0 == Some(index) && !rooms.is_changed() {
+        return;
+    }
+
+    current_room.0 = Some(index);
+    let room = rooms.0[index];
+    let center = room.center(&grid);
+
+    for (camera, mut transform) in cameras.iter_mut() {
+        if camera.name.as_deref() == Some(CameraPlugin::CAMERA_2D) {
+            transform.translation.x = center.x;
+            transform.translation.y = center.y;
+        }
+    }
+
+    for entity in backgrounds.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    commands
+        .spawn()
+        .insert(Cleanup)
+        .insert(RoomBackground)
+        .insert(GlobalTransform::default())
+        .insert(Transform::default())
+        .with_children(|parent| {
+            for y in room.origin.y..room.origin.y + room.height {
+                for x in room.origin.x..room.origin.x + room.width {
+                    parent.spawn_bundle(ColorMesh2dBundle {
+                        mesh: meshes.add(shape::Quad::default().into()).into(),
+                        material: materials.add(Color::GRAY.into()),
+                        transform: Transform::from_scale(Vec3::splat(grid.cell_size - 1.0))
+                            .with_translation(Vec3::new(
+                                x as f32 * grid.cell_size,
+                                y as f32 * grid.cell_size,
+                                0.0,
+                            )),
+                        ..Default::default()
+                    });
+                }
+            }
+        });
+}
@@ -0,0 +1,31 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::storage;
+
+const SAVE_PATH: &str = "save.ron";
+
+/// Progress kept between sessions, loaded whenever the game is entered.
+#[derive(Default, Serialize, Deserialize)]
+pub struct SaveData {
+    /// Hints taken so far, keyed by level name.
+    #[serde(default)]
+    pub hints_used: BTreeMap<String, u32>,
+}
+
+impl SaveData {
+    /// A missing or unreadable save starts afresh.
+    pub fn load() -> Self {
+        storage::read(SAVE_PATH)
+            .and_then(|save| ron::from_str(&save).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        storage::write(
+            SAVE_PATH,
+            &ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?,
+        )
+    }
+}
@@ -0,0 +1,991 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use anyhow::bail;
+
+use super::goals::{Goal, Goals};
+use super::BlockType;
+use super::Direction;
+use super::Discovery;
+use super::GridObject;
+use super::GridPosition;
+use super::LevelData;
+use super::LevelRules;
+use super::Mover;
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+];
+
+/// A single move: which character moves, which way, and whether it pulls what's behind it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Step {
+    pub character: usize,
+    pub direction: Direction,
+    pub grab: bool,
+}
+
+/// How a search for a solution ended.
+#[derive(Debug)]
+pub enum Search {
+    Solved(Vec<Step>),
+    /// Every reachable state was visited without meeting the goals.
+    Unsolvable,
+    /// The search reached its limit of states before it could tell.
+    GaveUp,
+}
+
+impl Search {
+    pub fn solution(self) -> Option<Vec<Step>> {
+        match self {
+            Search::Solved(steps) => Some(steps),
+            Search::Unsolvable | Search::GaveUp => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Block {
+    position: GridPosition,
+    /// Paint can change it.
+    kind: BlockType,
+    pushes_left: u32,
+    /// Only counted whilst it can still discover the block, so it doesn't grow forever.
+    times_pushed: u32,
+    discovered: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Enemy {
+    position: GridPosition,
+    /// How far along its path it is, wrapped to the path's length so states repeat.
+    step: usize,
+}
+
+/// Everything that can change whilst playing a level.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct State {
+    characters: Vec<GridPosition>,
+    blocks: Vec<Block>,
+    enemies: Vec<Enemy>,
+    gems: Vec<bool>,
+    /// Durability of each breakable wall, which is gone at zero.
+    walls_left: Vec<u32>,
+    /// Durability of each crumbling floor, which is a pit at zero.
+    floors_left: Vec<u32>,
+    /// Moves each pressure plate stays held once released, or none whilst it's up.
+    plates_held: Vec<Option<u32>>,
+    /// Only counted when the level limits pushes.
+    pushes: u32,
+}
+
+impl State {
+    pub fn pushes_left(&self, block: usize) -> u32 {
+        self.blocks[block].pushes_left
+    }
+
+    /// The blocks that are somewhere else in `next`.
+    pub fn moved_blocks<'a>(&'a self, next: &'a State) -> impl Iterator<Item = usize> + 'a {
+        self.blocks
+            .iter()
+            .zip(&next.blocks)
+            .enumerate()
+            .filter(|(_, (block, next))| block.position != next.position)
+            .map(|(index, _)| index)
+    }
+}
+
+/// What can't change about a block whilst playing.
+struct BlockRules {
+    pullable: bool,
+    discovery: Discovery,
+}
+
+/// The rules of a level, searchable without spawning it.
+///
+/// Only characters, walls, pits, breakable walls, crumbling floors, one way tiles, paint, push
+/// blocks, buttons, pressure plates, enemies, gems and exits are modelled, so levels using any
+/// other tile are refused.
+pub struct Puzzle {
+    walls: HashSet<GridPosition>,
+    breakable_walls: Vec<GridPosition>,
+    crumbling_floors: Vec<GridPosition>,
+    /// Tiles that only let some movers in, and only from one side.
+    one_ways: Vec<(GridPosition, GridObject)>,
+    paints: Vec<(GridPosition, BlockType)>,
+    /// Where each pressure plate is and how many moves it stays held after being released.
+    pressure_plates: Vec<(GridPosition, u32)>,
+    /// Cells outside these bounds are treated as walls so searches stay finite.
+    bounds: (GridPosition, GridPosition),
+    buttons: Vec<(GridPosition, BlockType)>,
+    exits: Vec<GridPosition>,
+    gem_positions: Vec<GridPosition>,
+    blocks: Vec<BlockRules>,
+    enemy_paths: Vec<Vec<Direction>>,
+    /// Paint or conveyors can recolour or carry blocks, which deadlock checks can't follow.
+    moves_blocks_itself: bool,
+    rules: LevelRules,
+    goals: Vec<Goal>,
+    start: State,
+}
+
+impl Puzzle {
+    /// Builds a puzzle from grid objects, which may come from a level file or a level in play.
+    /// Characters are numbered in the order they appear.
+    pub fn new<'a>(
+        objects: impl IntoIterator<Item = (&'a GridObject, &'a GridPosition)>,
+        rules: &LevelRules,
+        goals: &Goals,
+    ) -> anyhow::Result<Self> {
+        Self::build(objects, rules, goals, false)
+    }
+
+    /// Builds a puzzle that's only good for [`Puzzle::is_deadlocked`], which works for any
+    /// level. Pits count as walls and other tiles are left out, breakable walls included as
+    /// they can be worn away, so it mustn't be searched.
+    pub fn for_deadlocks<'a>(
+        objects: impl IntoIterator<Item = (&'a GridObject, &'a GridPosition)>,
+        rules: &LevelRules,
+        goals: &Goals,
+    ) -> anyhow::Result<Self> {
+        Self::build(objects, rules, goals, true)
+    }
+
+    fn build<'a>(
+        objects: impl IntoIterator<Item = (&'a GridObject, &'a GridPosition)>,
+        rules: &LevelRules,
+        goals: &Goals,
+        outline_only: bool,
+    ) -> anyhow::Result<Self> {
+        let mut puzzle = Puzzle {
+            walls: HashSet::new(),
+            breakable_walls: Vec::new(),
+            crumbling_floors: Vec::new(),
+            one_ways: Vec::new(),
+            paints: Vec::new(),
+            pressure_plates: Vec::new(),
+            bounds: (GridPosition { x: 0, y: 0 }, GridPosition { x: 0, y: 0 }),
+            buttons: Vec::new(),
+            exits: Vec::new(),
+            gem_positions: Vec::new(),
+            blocks: Vec::new(),
+            enemy_paths: Vec::new(),
+            moves_blocks_itself: false,
+            rules: rules.clone(),
+            goals: goals.0.clone(),
+            start: State {
+                characters: Vec::new(),
+                blocks: Vec::new(),
+                enemies: Vec::new(),
+                gems: Vec::new(),
+                walls_left: Vec::new(),
+                floors_left: Vec::new(),
+                plates_held: Vec::new(),
+                pushes: 0,
+            },
+        };
+
+        let mut positions = Vec::new();
+        for (object, position) in objects {
+            positions.push(*position);
+
+            match object {
+                GridObject::Player => puzzle.start.characters.push(*position),
+                GridObject::Wall | GridObject::Pit => {
+                    puzzle.walls.insert(*position);
+                }
+                GridObject::Button(kind, _) => puzzle.buttons.push((*position, kind.clone())),
+                GridObject::Exit => puzzle.exits.push(*position),
+                GridObject::Gem { collected } => {
+                    puzzle.gem_positions.push(*position);
+                    puzzle.start.gems.push(*collected);
+                }
+                GridObject::PushBlock {
+                    kind,
+                    pushes_left,
+                    pullable,
+                    discovery,
+                    times_pushed,
+                    discovered,
+                } => {
+                    let discovery = discovery.unwrap_or(rules.discovery);
+
+                    puzzle.blocks.push(BlockRules {
+                        pullable: *pullable,
+                        discovery,
+                    });
+                    puzzle.start.blocks.push(Block {
+                        position: *position,
+                        kind: kind.clone(),
+                        pushes_left: *pushes_left,
+                        times_pushed: *times_pushed,
+                        discovered: *discovered || discovery == Discovery::Known,
+                    });
+                }
+                GridObject::Paint(_) | GridObject::Conveyor(_) if outline_only => {
+                    puzzle.moves_blocks_itself = true;
+                }
+                // Tiles with no durability left still last until they're next worn down
+                GridObject::BreakableWall { durability } => {
+                    puzzle.breakable_walls.push(*position);
+                    puzzle.start.walls_left.push((*durability).max(1));
+                }
+                GridObject::CrumblingFloor { durability } if !outline_only => {
+                    puzzle.crumbling_floors.push(*position);
+                    puzzle.start.floors_left.push((*durability).max(1));
+                }
+                GridObject::OneWay { .. } if !outline_only => {
+                    puzzle.one_ways.push((*position, object.clone()));
+                }
+                GridObject::Paint(kind) if !outline_only => {
+                    puzzle.paints.push((*position, kind.clone()));
+                }
+                GridObject::PressurePlate {
+                    hold_moves,
+                    pressed,
+                    release_moves_left,
+                } if !outline_only => {
+                    puzzle.pressure_plates.push((*position, *hold_moves));
+                    puzzle
+                        .start
+                        .plates_held
+                        .push(pressed.then(|| *release_moves_left));
+                }
+                GridObject::Enemy { path, step } if !outline_only => {
+                    puzzle.enemy_paths.push(path.clone());
+                    puzzle.start.enemies.push(Enemy {
+                        position: *position,
+                        step: step.checked_rem(path.len()).unwrap_or(0),
+                    });
+                }
+                _ if outline_only => {}
+                other => bail!("{:?} can't be solved for", other),
+            }
+        }
+
+        if puzzle.start.characters.is_empty() {
+            bail!("Level has no characters");
+        }
+
+        let min_x = positions.iter().map(|position| position.x).min().unwrap_or(0);
+        let min_y = positions.iter().map(|position| position.y).min().unwrap_or(0);
+        let max_x = positions.iter().map(|position| position.x).max().unwrap_or(0);
+        let max_y = positions.iter().map(|position| position.y).max().unwrap_or(0);
+        puzzle.bounds = (
+            GridPosition { x: min_x - 1, y: min_y - 1 },
+            GridPosition { x: max_x + 1, y: max_y + 1 },
+        );
+
+        Ok(puzzle)
+    }
+
+    pub fn from_level(level: &LevelData) -> anyhow::Result<Self> {
+        Self::new(
+            level.objects.iter().map(|(object, position)| (object, position)),
+            &level.rules,
+            &level.goals,
+        )
+    }
+
+    pub fn start(&self) -> &State {
+        &self.start
+    }
+
+    /// The start state once `pushes` pushes have already been made, for solving a level in play.
+    pub fn start_after(&self, pushes: u32) -> State {
+        let mut state = self.start.clone();
+        if self.rules.max_pushes.is_some() {
+            state.pushes = pushes;
+        }
+        state
+    }
+
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// The colour `block` starts with, before any paint.
+    pub fn block_kind(&self, block: usize) -> &BlockType {
+        &self.start.blocks[block].kind
+    }
+
+    /// Whether every goal is met in `state`.
+    pub fn is_solved(&self, state: &State) -> bool {
+        let is_met = |goal: &Goal| match goal {
+            Goal::AllButtons => {
+                self.buttons.iter().all(|(button, kind)| {
+                    state.blocks.iter().any(|block| {
+                        block.position == *button && block.discovered && block.kind == *kind
+                    })
+                }) && state.plates_held.iter().all(Option::is_some)
+            }
+            Goal::ReachExit => state
+                .characters
+                .iter()
+                .any(|character| self.exits.contains(character)),
+            Goal::CollectAllGems => state.gems.iter().all(|collected| *collected),
+            Goal::AllBlocksExhausted => state.blocks.iter().all(|block| block.pushes_left == 0),
+        };
+
+        self.goals.iter().all(is_met)
+    }
+
+    /// Every state reachable with a single move from `state`.
+    pub fn successors(&self, state: &State) -> Vec<(Step, State)> {
+        let mut successors = Vec::new();
+
+        for character in 0..state.characters.len() {
+            for direction in DIRECTIONS {
+                let step = Step {
+                    character,
+                    direction,
+                    grab: false,
+                };
+
+                let moved = self.apply(state, step);
+                let pulled = self.apply(state, Step { grab: true, ..step });
+
+                // Grabbing only makes a difference with a pullable block behind the character
+                if let Some(pulled) = pulled.filter(|pulled| moved.as_ref() != Some(pulled)) {
+                    successors.push((Step { grab: true, ..step }, pulled));
+                }
+                if let Some(moved) = moved {
+                    successors.push((step, moved));
+                }
+            }
+        }
+
+        successors
+    }
+
+    /// The shortest solution from the start, or none if the level can't be solved whilst
+    /// visiting fewer than `max_states` states.
+    pub fn solve(&self, max_states: usize) -> Option<Vec<Step>> {
+        self.solve_from(&self.start, 0, max_states).solution()
+    }
+
+    /// Searches for the shortest solution from `state`, having already made `moves` moves.
+    pub fn solve_from(&self, state: &State, moves: u32, max_states: usize) -> Search {
+        if self.is_solved(state) {
+            return Search::Solved(Vec::new());
+        }
+
+        let mut parents: HashMap<State, (State, Step)> = HashMap::new();
+        let mut queue = VecDeque::from([(state.clone(), moves)]);
+
+        while let Some((current, moves)) = queue.pop_front() {
+            if matches!(self.rules.max_moves, Some(max_moves) if moves >= max_moves) {
+                continue;
+            }
+
+            for (step, next) in self.successors(&current) {
+                if next == *state || parents.contains_key(&next) {
+                    continue;
+                }
+
+                parents.insert(next.clone(), (current.clone(), step));
+
+                if self.is_solved(&next) {
+                    let mut steps = vec![step];
+                    let mut cursor = current.clone();
+
+                    while cursor != *state {
+                        let (previous, step) = parents[&cursor].clone();
+                        steps.push(step);
+                        cursor = previous;
+                    }
+
+                    steps.reverse();
+                    return Search::Solved(steps);
+                }
+
+                if parents.len() >= max_states {
+                    return Search::GaveUp;
+                }
+
+                queue.push_back((next, moves + 1));
+            }
+        }
+
+        Search::Unsolvable
+    }
+
+    /// Plays `step` from `state` the same way the game does, or returns none if it's blocked.
+    pub fn apply(&self, state: &State, step: Step) -> Option<State> {
+        let offset = step.direction.offset();
+        let from = *state.characters.get(step.character)?;
+        let to = from.offset(offset);
+        let out_of_pushes =
+            matches!(self.rules.max_pushes, Some(max_pushes) if state.pushes >= max_pushes);
+
+        let mut next = state.clone();
+        let mut moved_blocks = Vec::new();
+
+        // Every block in the row in front of the character, nearest first
+        let mut chain = Vec::new();
+        let mut cursor = to;
+        while let Some(block) = self.block_at(state, cursor) {
+            chain.push(block);
+            cursor = cursor.offset(offset);
+        }
+
+        if !chain.is_empty() {
+            let chain_is_movable = chain.iter().all(|block| self.is_movable(state, *block));
+
+            if !chain_is_movable
+                || chain.len() > self.rules.max_push_chain as usize
+                || out_of_pushes
+            {
+                return None;
+            }
+
+            // Blocks in the row all move at once, so only the tiles underneath them matter
+            let can_move = self.is_free(state, cursor, Mover::Block, offset)
+                && chain.iter().all(|block| {
+                    let target = state.blocks[*block].position.offset(offset);
+                    self.allows_entry(target, Mover::Block, offset)
+                })
+                && self.allows_entry(to, Mover::Player, offset);
+
+            if !can_move {
+                // Shoving the row into a breakable wall wears it down without anything moving
+                let wall = self.standing_wall_at(state, cursor)?;
+                next.walls_left[wall] -= 1;
+                return self.end_turn(next);
+            }
+
+            for block in chain {
+                next.blocks[block].position = next.blocks[block].position.offset(offset);
+                moved_blocks.push(block);
+            }
+        } else {
+            if !self.is_free(state, to, Mover::Player, offset) {
+                return None;
+            }
+
+            let behind = from.offset((-offset.0, -offset.1));
+            let pulled = self.block_at(state, behind).filter(|block| {
+                step.grab
+                    && !out_of_pushes
+                    && self.blocks[*block].pullable
+                    && self.is_movable(state, *block)
+                    && !self.crumbles_when_left(state, from)
+                    && self.allows_entry(from, Mover::Block, offset)
+            });
+
+            if let Some(block) = pulled {
+                next.blocks[block].position = from;
+                moved_blocks.push(block);
+            }
+        }
+
+        next.characters[step.character] = to;
+
+        if let Some(floor) = self.crumbling_floors.iter().position(|floor| *floor == from) {
+            next.floors_left[floor] -= 1;
+        }
+
+        if !moved_blocks.is_empty() && self.rules.max_pushes.is_some() {
+            next.pushes += 1;
+        }
+
+        for block in &moved_blocks {
+            let rules = &self.blocks[*block];
+            let block = &mut next.blocks[*block];
+
+            if let Some((_, paint)) = self.paints.iter().find(|(paint, _)| *paint == block.position)
+            {
+                block.kind = paint.clone();
+            }
+
+            block.pushes_left = block.pushes_left.saturating_sub(1);
+            if let Discovery::AfterPushes(_) = rules.discovery {
+                if !block.discovered {
+                    block.times_pushed += 1;
+                }
+            }
+        }
+
+        for block in moved_blocks {
+            if next.blocks[block].discovered || !self.is_discovered_now(&next, block) {
+                continue;
+            }
+
+            if self.rules.reveal_matching {
+                let kind = next.blocks[block].kind.clone();
+                for other in next.blocks.iter_mut() {
+                    if other.kind == kind {
+                        other.discovered = true;
+                    }
+                }
+            } else {
+                next.blocks[block].discovered = true;
+            }
+        }
+
+        for (collected, gem) in next.gems.iter_mut().zip(&self.gem_positions) {
+            *collected |= next.characters.contains(gem);
+        }
+
+        self.end_turn(next)
+    }
+
+    /// Everything that happens after every move, once the character's move has resolved. Returns
+    /// none if an enemy catches a character, failing the level.
+    fn end_turn(&self, mut state: State) -> Option<State> {
+        // Enemies take their turns in order of position, as in the game
+        let mut enemies: Vec<usize> = (0..state.enemies.len()).collect();
+        enemies.sort_by_key(|enemy| {
+            let position = state.enemies[*enemy].position;
+            (position.x, position.y)
+        });
+
+        for enemy in enemies {
+            let path = &self.enemy_paths[enemy];
+            let Enemy { position, step } = state.enemies[enemy];
+            let direction = match path.get(step) {
+                Some(direction) => direction.offset(),
+                None => continue,
+            };
+            let new_position = position.offset(direction);
+
+            if state.characters.contains(&new_position) {
+                return None;
+            }
+
+            if self.is_free(&state, new_position, Mover::Enemy, direction) {
+                state.enemies[enemy] = Enemy {
+                    position: new_position,
+                    step: (step + 1) % path.len(),
+                };
+            }
+        }
+
+        for (held, (plate, hold_moves)) in state.plates_held.iter_mut().zip(&self.pressure_plates) {
+            let is_occupied = state.characters.contains(plate)
+                || state.blocks.iter().any(|block| block.position == *plate);
+
+            *held = if is_occupied {
+                Some(*hold_moves)
+            } else {
+                held.and_then(|moves_left| moves_left.checked_sub(1))
+            };
+        }
+
+        Some(state)
+    }
+
+    fn block_at(&self, state: &State, position: GridPosition) -> Option<usize> {
+        state
+            .blocks
+            .iter()
+            .position(|block| block.position == position)
+    }
+
+    /// Whether `mover` could move into `position` whilst travelling by `offset`.
+    fn is_free(
+        &self,
+        state: &State,
+        position: GridPosition,
+        mover: Mover,
+        offset: (i32, i32),
+    ) -> bool {
+        self.is_floor(position)
+            && self.allows_entry(position, mover, offset)
+            && !state.characters.contains(&position)
+            && !state.enemies.iter().any(|enemy| enemy.position == position)
+            && self.block_at(state, position).is_none()
+            && self.standing_wall_at(state, position).is_none()
+            && !self
+                .crumbling_floors
+                .iter()
+                .zip(&state.floors_left)
+                .any(|(floor, durability)| *floor == position && *durability == 0)
+    }
+
+    /// Whether any one way tile at `position` lets `mover` in whilst travelling by `offset`.
+    fn allows_entry(&self, position: GridPosition, mover: Mover, offset: (i32, i32)) -> bool {
+        self.one_ways
+            .iter()
+            .filter(|(one_way, _)| *one_way == position)
+            .all(|(_, object)| object.allows_entry(mover, offset))
+    }
+
+    /// The breakable wall at `position`, if it hasn't broken yet.
+    fn standing_wall_at(&self, state: &State, position: GridPosition) -> Option<usize> {
+        self.breakable_walls
+            .iter()
+            .zip(&state.walls_left)
+            .position(|(wall, durability)| *wall == position && *durability > 0)
+    }
+
+    /// Whether the floor at `position` turns into a pit once a character leaves it.
+    fn crumbles_when_left(&self, state: &State, position: GridPosition) -> bool {
+        self.crumbling_floors
+            .iter()
+            .zip(&state.floors_left)
+            .any(|(floor, durability)| *floor == position && *durability == 1)
+    }
+
+    /// Whether `position` is never blocked by a wall, whatever else moves around.
+    fn is_floor(&self, position: GridPosition) -> bool {
+        let (min, max) = self.bounds;
+
+        (min.x..=max.x).contains(&position.x)
+            && (min.y..=max.y).contains(&position.y)
+            && !self.walls.contains(&position)
+    }
+
+    /// Whether a state is plainly unsolvable without searching, because some colour has fewer
+    /// blocks that could still reach its buttons than it has buttons. It never reports a state
+    /// that can still be solved, but can miss ones that can't.
+    pub fn is_deadlocked(&self, state: &State) -> bool {
+        if self.moves_blocks_itself || !self.goals.contains(&Goal::AllButtons) {
+            return false;
+        }
+
+        let out_of_pushes =
+            matches!(self.rules.max_pushes, Some(max_pushes) if state.pushes >= max_pushes);
+
+        let mut kinds: Vec<&BlockType> = Vec::new();
+        for (_, kind) in &self.buttons {
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+
+        kinds.into_iter().any(|kind| {
+            let buttons: Vec<GridPosition> = self
+                .buttons
+                .iter()
+                .filter(|(_, button_kind)| button_kind == kind)
+                .map(|(position, _)| *position)
+                .collect();
+
+            let live_blocks = state
+                .blocks
+                .iter()
+                .zip(&self.blocks)
+                .enumerate()
+                .filter(|(_, (block, _))| block.kind == *kind)
+                .filter(|(index, (block, rules))| {
+                    if buttons.contains(&block.position) {
+                        return true;
+                    }
+
+                    // Run out of pushes or wedged in a corner it can't be pulled out of
+                    if out_of_pushes
+                        || !self.is_movable(state, *index)
+                        || (!rules.pullable && self.is_cornered(block.position))
+                    {
+                        return false;
+                    }
+
+                    buttons.iter().any(|button| {
+                        self.can_reach_button(block.position, *button, rules.pullable)
+                    })
+                })
+                .count();
+
+            live_blocks < buttons.len()
+        })
+    }
+
+    /// Whether walls on two adjacent sides stop a block at `position` being pushed anywhere.
+    fn is_cornered(&self, position: GridPosition) -> bool {
+        let is_blocked = |direction: Direction| !self.is_floor(position.offset(direction.offset()));
+
+        (is_blocked(Direction::Up) || is_blocked(Direction::Down))
+            && (is_blocked(Direction::Left) || is_blocked(Direction::Right))
+    }
+
+    /// Whether a block could be moved from `from` onto `button` if nothing else was in the way.
+    fn can_reach_button(&self, from: GridPosition, button: GridPosition, pullable: bool) -> bool {
+        // Work backwards from the button through every cell the block could have come from
+        let mut reached = HashSet::from([button]);
+        let mut queue = VecDeque::from([button]);
+
+        while let Some(to) = queue.pop_front() {
+            for direction in DIRECTIONS {
+                let (x, y) = direction.offset();
+                let previous = to.offset((-x, -y));
+
+                // Pushed from behind, or pulled by a character stepping away ahead of it
+                let can_push = self.is_floor(previous.offset((-x, -y)));
+                let can_pull = pullable && self.is_floor(to.offset((x, y)));
+
+                if self.is_floor(previous) && (can_push || can_pull) && reached.insert(previous) {
+                    if previous == from {
+                        return true;
+                    }
+                    queue.push_back(previous);
+                }
+            }
+        }
+
+        reached.contains(&from)
+    }
+
+    fn is_movable(&self, state: &State, block: usize) -> bool {
+        self.blocks[block].discovery != Discovery::Known || state.blocks[block].pushes_left > 0
+    }
+
+    fn is_discovered_now(&self, state: &State, block: usize) -> bool {
+        let rules = &self.blocks[block];
+        let block = &state.blocks[block];
+
+        match rules.discovery {
+            Discovery::OutOfPushes => block.pushes_left == 0,
+            Discovery::AfterPushes(pushes) => block.times_pushed >= pushes,
+            Discovery::NextToButton => self.buttons.iter().any(|(button, kind)| {
+                let distance =
+                    (button.x - block.position.x).abs() + (button.y - block.position.y).abs();
+
+                distance == 1 && *kind == block.kind
+            }),
+            Discovery::Known => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::in_game::ascii;
+
+    fn puzzle(ascii: &str) -> Puzzle {
+        Puzzle::from_level(&ascii::parse(ascii).unwrap()).unwrap()
+    }
+
+    fn step(direction: Direction, grab: bool) -> Step {
+        Step {
+            character: 0,
+            direction,
+            grab,
+        }
+    }
+
+    #[test]
+    fn finds_the_shortest_solution() {
+        let puzzle = puzzle(
+            "######\n\
+            #@...#\n\
+            #.a.b#\n\
+            #....#\n\
+            ######\n\
+            a: [PushBlock(kind:\"Red\",pushes_left:5,discovery:Some(Known))]\n\
+            b: [Button(\"Red\")]\n",
+        );
+
+        assert_eq!(
+            puzzle.solve(10_000),
+            Some(vec![
+                step(Direction::Down, false),
+                step(Direction::Right, false),
+                step(Direction::Right, false),
+            ])
+        );
+    }
+
+    #[test]
+    fn reports_a_cornered_block_as_unsolvable() {
+        let puzzle = puzzle(
+            "#####\n\
+            #a..#\n\
+            #.@.#\n\
+            #..b#\n\
+            #####\n\
+            a: [PushBlock(kind:\"Red\",pushes_left:5,discovery:Some(Known))]\n\
+            b: [Button(\"Red\")]\n",
+        );
+
+        assert!(matches!(
+            puzzle.solve_from(puzzle.start(), 0, 10_000),
+            Search::Unsolvable
+        ));
+    }
+
+    #[test]
+    fn pulls_blocks_away_from_walls() {
+        let level = "######\n\
+            #a@b.#\n\
+            ######\n\
+            b: [Button(\"Red\")]\n";
+
+        let pullable = puzzle(&format!(
+            "{}a: [PushBlock(kind:\"Red\",pushes_left:5,pullable:true,discovery:Some(Known))]\n",
+            level
+        ));
+        assert_eq!(
+            pullable.solve(10_000),
+            Some(vec![
+                step(Direction::Right, true),
+                step(Direction::Right, true),
+            ])
+        );
+
+        let pushable = puzzle(&format!(
+            "{}a: [PushBlock(kind:\"Red\",pushes_left:5,discovery:Some(Known))]\n",
+            level
+        ));
+        assert!(matches!(
+            pushable.solve_from(pushable.start(), 0, 10_000),
+            Search::Unsolvable
+        ));
+    }
+
+    #[test]
+    fn pushes_rows_up_to_the_chain_limit() {
+        let level = "#######\n\
+            #@aa..#\n\
+            #######\n\
+            a: [PushBlock(kind:\"Red\",pushes_left:5,discovery:Some(Known))]\n";
+        let push = step(Direction::Right, false);
+
+        let single = puzzle(level);
+        assert!(single.apply(single.start(), push).is_none());
+
+        let chain = puzzle(&format!("rules: (max_push_chain: 2)\n{}", level));
+        let next = chain.apply(chain.start(), push).unwrap();
+        assert_eq!(chain.start().moved_blocks(&next).count(), 2);
+        assert_eq!(next.characters, vec![GridPosition { x: 2, y: -1 }]);
+    }
+
+    #[test]
+    fn only_enters_one_way_tiles_from_their_side() {
+        let puzzle = puzzle(
+            "#####\n\
+            #@o.#\n\
+            #####\n\
+            o: [OneWay(direction:Left,player:true,blocks:true)]\n",
+        );
+
+        assert!(puzzle
+            .apply(puzzle.start(), step(Direction::Right, false))
+            .is_none());
+    }
+
+    #[test]
+    fn repaints_blocks_pushed_over_paint() {
+        let puzzle = puzzle(
+            "######\n\
+            #@apb#\n\
+            ######\n\
+            a: [PushBlock(kind:\"Red\",pushes_left:5,discovery:Some(Known))]\n\
+            b: [Button(\"Green\")]\n\
+            p: [Paint(\"Green\")]\n",
+        );
+
+        assert_eq!(
+            puzzle.solve(10_000),
+            Some(vec![
+                step(Direction::Right, false),
+                step(Direction::Right, false),
+            ])
+        );
+        assert_eq!(puzzle.block_kind(0), &BlockType("Red".to_string()));
+    }
+
+    #[test]
+    fn holds_pressure_plates_down_after_they_are_left() {
+        let puzzle = puzzle(
+            "######\n\
+            #@P..#\n\
+            ######\n\
+            P: [PressurePlate(hold_moves:1)]\n",
+        );
+        let right = step(Direction::Right, false);
+
+        let pressed = puzzle.apply(puzzle.start(), right).unwrap();
+        assert!(puzzle.is_solved(&pressed));
+
+        let held = puzzle.apply(&pressed, right).unwrap();
+        assert!(puzzle.is_solved(&held));
+
+        let released = puzzle.apply(&held, right).unwrap();
+        assert!(!puzzle.is_solved(&released));
+    }
+
+    #[test]
+    fn refuses_moves_that_get_characters_caught() {
+        let puzzle = puzzle(
+            "#######\n\
+            #@...e#\n\
+            #######\n\
+            e: [Enemy(path:[Left,Left,Right,Right])]\n",
+        );
+        let right = step(Direction::Right, false);
+
+        let next = puzzle.apply(puzzle.start(), right).unwrap();
+        assert_eq!(
+            next.enemies,
+            vec![Enemy {
+                position: GridPosition { x: 4, y: -1 },
+                step: 1,
+            }]
+        );
+
+        assert!(puzzle.apply(&next, right).is_none());
+        assert!(puzzle
+            .apply(&next, step(Direction::Left, false))
+            .is_some());
+    }
+
+    fn deadlocks(ascii: &str) -> Puzzle {
+        let level = ascii::parse(ascii).unwrap();
+        Puzzle::for_deadlocks(
+            level.objects.iter().map(|(object, position)| (object, position)),
+            &level.rules,
+            &level.goals,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn spots_blocks_wedged_in_corners() {
+        let puzzle = deadlocks(
+            "#####\n\
+            #a..#\n\
+            #.@b#\n\
+            #####\n\
+            a: [PushBlock(kind:\"Red\",pushes_left:5)]\n\
+            b: [Button(\"Red\")]\n",
+        );
+
+        assert!(puzzle.is_deadlocked(puzzle.start()));
+    }
+
+    #[test]
+    fn sees_past_walls_that_can_be_broken() {
+        let puzzle = deadlocks(
+            "####\n\
+            #w@#\n\
+            #a.#\n\
+            #b.#\n\
+            ####\n\
+            a: [PushBlock(kind:\"Red\",pushes_left:5)]\n\
+            b: [Button(\"Red\")]\n\
+            w: [BreakableWall(durability:2)]\n",
+        );
+
+        assert!(!puzzle.is_deadlocked(puzzle.start()));
+    }
+
+    #[test]
+    fn leaves_levels_that_move_blocks_themselves_alone() {
+        let puzzle = deadlocks(
+            "#####\n\
+            #a..#\n\
+            #.@b#\n\
+            #..p#\n\
+            #####\n\
+            a: [PushBlock(kind:\"Red\",pushes_left:5)]\n\
+            b: [Button(\"Green\")]\n\
+            p: [Paint(\"Green\")]\n",
+        );
+
+        assert!(!puzzle.is_deadlocked(puzzle.start()));
+    }
+}
@@ -0,0 +1,32 @@
+//! Small text files kept between sessions: on disk natively and in local storage on the web,
+//! where there's no file system.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read(name: &str) -> Option<String> {
+    std::fs::read_to_string(name).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write(name: &str, contents: &str) -> anyhow::Result<()> {
+    std::fs::write(name, contents)?;
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read(name: &str) -> Option<String> {
+    local_storage()?.get_item(name).ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write(name: &str, contents: &str) -> anyhow::Result<()> {
+    let storage =
+        local_storage().ok_or_else(|| anyhow::anyhow!("Local storage isn't available"))?;
+    storage
+        .set_item(name, contents)
+        .map_err(|_| anyhow::anyhow!("Couldn't write {} to local storage", name))
+}
@@ -0,0 +1,114 @@
+use bevy::prelude::*;
+
+use crate::app_state::AppState;
+
+use super::goals::Goals;
+use super::intro::LevelInfo;
+use super::migration;
+use super::rooms::Rooms;
+use super::CharacterIndex;
+use super::GridObject;
+use super::GridPosition;
+use super::LevelData;
+use super::LevelOutcome;
+use super::LevelRules;
+use super::MoveCount;
+use super::Palette;
+
+/// The level as it stood before a move, with enough to put it back exactly.
+pub struct Turn {
+    /// Characters come last, in index order, so they're numbered the same when respawned.
+    objects: Vec<(GridObject, GridPosition)>,
+    active_character: usize,
+    move_count: MoveCount,
+}
+
+impl Turn {
+    pub fn new<'a>(
+        objects: impl IntoIterator<
+            Item = (&'a GridObject, &'a GridPosition, Option<&'a CharacterIndex>),
+        >,
+        active_character: usize,
+        move_count: &MoveCount,
+    ) -> Self {
+        let mut objects: Vec<_> = objects.into_iter().collect();
+        objects.sort_by_key(|(_, _, index)| index.copied());
+
+        Self {
+            objects: objects
+                .into_iter()
+                .map(|(object, position, _)| (object.clone(), *position))
+                .collect(),
+            active_character,
+            move_count: move_count.clone(),
+        }
+    }
+}
+
+/// Every turn taken since the level was spawned, most recent last.
+#[derive(Default)]
+pub struct UndoHistory(pub Vec<Turn>);
+
+/// The last move was taken back, so anything worked out from the level needs redoing.
+pub struct TurnUndoneEvent;
+
+pub struct UndoPlugin;
+impl Plugin for UndoPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TurnUndoneEvent>()
+            .add_system_set(SystemSet::on_update(AppState::InGame).with_system(undo_turn));
+    }
+}
+
+/// Respawns the level as it was before the last move, even once the level has been failed.
+#[allow(clippy::too_many_arguments)]
+fn undo_turn(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    level_objects: Query<Entity, With<GridPosition>>,
+    mut history: ResMut<UndoHistory>,
+    rules: Res<LevelRules>,
+    palette: Res<Palette>,
+    mut move_count: ResMut<MoveCount>,
+    mut outcome: ResMut<LevelOutcome>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    mut turn_undone_events: EventWriter<TurnUndoneEvent>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::U)
+        || !matches!(*outcome, LevelOutcome::Playing | LevelOutcome::Failed)
+    {
+        return;
+    }
+
+    let turn = match history.0.pop() {
+        Some(turn) => turn,
+        None => return,
+    };
+
+    for entity in level_objects.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let level = LevelData {
+        version: migration::LATEST_VERSION,
+        info: LevelInfo::default(),
+        rules: rules.clone(),
+        palette: palette.clone(),
+        goals: Goals::default(),
+        rooms: Rooms::default(),
+        objects: turn.objects,
+    };
+    level.spawn(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &asset_server,
+        turn.active_character,
+    );
+
+    *move_count = turn.move_count;
+    *outcome = LevelOutcome::Playing;
+    turn_undone_events.send(TurnUndoneEvent);
+}
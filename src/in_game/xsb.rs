@@ -0,0 +1,263 @@
+use anyhow::bail;
+
+use super::goals::Goals;
+use super::intro::LevelInfo;
+use super::rooms::{Room, Rooms};
+use super::Discovery;
+use super::GridObject;
+use super::GridPosition;
+use super::migration;
+use super::LevelData;
+use super::LevelRules;
+use super::Palette;
+
+/// How Sokoban boxes and goals are turned into blocks and buttons.
+pub struct XsbOptions {
+    /// How many palette colours to deal out to boxes and goals in reading order. With one
+    /// colour any box fits any goal, just like in Sokoban.
+    pub colours: usize,
+    pub pushes_left: u32,
+    pub discovery: Discovery,
+}
+
+impl Default for XsbOptions {
+    fn default() -> Self {
+        Self {
+            colours: 1,
+            pushes_left: 20,
+            discovery: Discovery::Known,
+        }
+    }
+}
+
+/// Converts every level in an XSB file. Levels are runs of board rows, separated by blank
+/// lines, titles or comments.
+pub fn parse_xsb(xsb: &str, options: &XsbOptions) -> anyhow::Result<Vec<LevelData>> {
+    let mut levels = Vec::new();
+    let mut rows: Vec<String> = Vec::new();
+
+    for line in xsb.lines() {
+        match board_row(line) {
+            Some(row) => rows.extend(row.split('|').map(str::to_string)),
+            None if !rows.is_empty() => {
+                levels.push(parse_level(&rows, options)?);
+                rows.clear();
+            }
+            None => {}
+        }
+    }
+
+    if !rows.is_empty() {
+        levels.push(parse_level(&rows, options)?);
+    }
+
+    if levels.is_empty() {
+        bail!("No levels found");
+    }
+
+    Ok(levels)
+}
+
+/// The line with any run lengths expanded if it's part of a board, so `4#` becomes `####`.
+fn board_row(line: &str) -> Option<String> {
+    let line = line.trim_end();
+    let is_board = !line.is_empty()
+        && line.contains('#')
+        && line
+            .chars()
+            .all(|tile| "#@+$*.-_ |".contains(tile) || tile.is_ascii_digit());
+
+    if !is_board {
+        return None;
+    }
+
+    let mut row = String::new();
+    let mut run = 0;
+    for tile in line.chars() {
+        match tile.to_digit(10) {
+            Some(digit) => run = run * 10 + digit as usize,
+            None => {
+                row.push_str(&tile.to_string().repeat(run.max(1)));
+                run = 0;
+            }
+        }
+    }
+
+    Some(row)
+}
+
+fn parse_level(rows: &[String], options: &XsbOptions) -> anyhow::Result<LevelData> {
+    let palette = Palette::default();
+    let colours: Vec<_> = palette
+        .0
+        .iter()
+        .take(options.colours.max(1))
+        .map(|entry| entry.id.clone())
+        .collect();
+
+    let mut objects = Vec::new();
+    let mut blocks = 0;
+    let mut buttons = 0;
+
+    for (row, tiles) in rows.iter().enumerate() {
+        for (column, tile) in tiles.chars().enumerate() {
+            // The first row is at the top of the level
+            let position = GridPosition {
+                x: column as i32,
+                y: -(row as i32),
+            };
+
+            if matches!(tile, '.' | '*' | '+') {
+                let kind = colours[buttons % colours.len()].clone();
+                objects.push((GridObject::Button(kind, None), position));
+                buttons += 1;
+            }
+
+            match tile {
+                '#' => objects.push((GridObject::Wall, position)),
+                '@' | '+' => objects.push((GridObject::Player, position)),
+                '$' | '*' => {
+                    objects.push((
+                        GridObject::PushBlock {
+                            kind: colours[blocks % colours.len()].clone(),
+                            pushes_left: options.pushes_left,
+                            pullable: false,
+                            discovery: None,
+                            times_pushed: 0,
+                            discovered: false,
+                        },
+                        position,
+                    ));
+                    blocks += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if !objects
+        .iter()
+        .any(|(object, _)| matches!(object, GridObject::Player))
+    {
+        bail!("Level has no player");
+    }
+
+    if blocks != buttons {
+        bail!("Level has {} boxes but {} goals", blocks, buttons);
+    }
+
+    let width = rows
+        .iter()
+        .map(|row| row.chars().count())
+        .max()
+        .unwrap_or(0) as i32;
+    let height = rows.len() as i32;
+
+    Ok(LevelData {
+        version: migration::LATEST_VERSION,
+        info: LevelInfo::default(),
+        rules: LevelRules {
+            discovery: options.discovery,
+            ..Default::default()
+        },
+        palette,
+        goals: Goals::default(),
+        rooms: Rooms(vec![Room {
+            origin: GridPosition {
+                x: 0,
+                y: 1 - height,
+            },
+            width,
+            height,
+        }]),
+        objects,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::in_game::BlockType;
+
+    const TWO_LEVELS: &str = "; A small pack\n\
+        Title: First\n\
+        #####\n\
+        #@$.#\n\
+        #####\n\
+        \n\
+        Title: Second\n\
+        5#\n\
+        #+*-$$.#|4#\n";
+
+    fn count(level: &LevelData, matches: impl Fn(&GridObject) -> bool) -> usize {
+        level
+            .objects
+            .iter()
+            .filter(|(object, _)| matches(object))
+            .count()
+    }
+
+    #[test]
+    fn splits_levels_on_titles_and_blank_lines() {
+        let levels = parse_xsb(TWO_LEVELS, &XsbOptions::default()).unwrap();
+        assert_eq!(levels.len(), 2);
+
+        let first = &levels[0];
+        let walls = count(first, |object| matches!(object, GridObject::Wall));
+        assert_eq!(walls, 12);
+        assert!(first.objects.iter().any(|(object, position)| {
+            matches!(object, GridObject::Player) && *position == GridPosition { x: 1, y: -1 }
+        }));
+        assert_eq!(first.rooms.0[0].width, 5);
+        assert_eq!(first.rooms.0[0].height, 3);
+    }
+
+    #[test]
+    fn expands_run_lengths_and_row_separators() {
+        let levels = parse_xsb(TWO_LEVELS, &XsbOptions::default()).unwrap();
+        let second = &levels[1];
+
+        // The player on a goal and a box on a goal each add a button under them
+        let buttons = count(second, |object| matches!(object, GridObject::Button(_, _)));
+        let blocks = count(second, |object| {
+            matches!(object, GridObject::PushBlock { .. })
+        });
+        assert_eq!((buttons, blocks), (3, 3));
+        assert_eq!(second.rooms.0[0].height, 3);
+    }
+
+    #[test]
+    fn deals_out_colours_in_reading_order() {
+        let options = XsbOptions {
+            colours: 2,
+            ..Default::default()
+        };
+        let level = &parse_xsb("######\n#@$$..#\n######", &options).unwrap()[0];
+
+        let kinds: Vec<&BlockType> = level
+            .objects
+            .iter()
+            .filter_map(|(object, _)| match object {
+                GridObject::PushBlock { kind, .. } => Some(kind),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(kinds.len(), 2);
+        assert_ne!(kinds[0], kinds[1]);
+    }
+
+    #[test]
+    fn loads_like_a_level_file() {
+        let level = &parse_xsb(TWO_LEVELS, &XsbOptions::default()).unwrap()[0];
+        let reloaded = LevelData::from_ron(&level.to_ron().unwrap()).unwrap();
+        assert_eq!(reloaded.objects.len(), level.objects.len());
+    }
+
+    #[test]
+    fn rejects_broken_levels() {
+        let options = XsbOptions::default();
+        assert!(parse_xsb("no levels here", &options).is_err());
+        assert!(parse_xsb("#####\n#-$.#\n#####", &options).is_err());
+        assert!(parse_xsb("#####\n#@$-#\n#####", &options).is_err());
+    }
+}
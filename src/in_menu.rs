@@ -1,12 +1,14 @@
 use bevy::{prelude::*, math::Rect};
 
 use crate::app_state::*;
+use crate::in_game::{LevelData, DEFAULT_LEVEL_PATH};
 
 pub struct InMenuStatePlugin;
 impl Plugin for InMenuStatePlugin {
     fn build(&self, app: &mut App) {
         app
             .add_state(AppState::Menu)
+            .init_resource::<GameMode>()
             .add_system_set(
                 SystemSet::on_exit(AppState::Menu)
                 .with_system(on_exit)
@@ -14,6 +16,7 @@ impl Plugin for InMenuStatePlugin {
             .add_system_set(
                 SystemSet::on_update(AppState::Menu)
                     .with_system(play_button)
+                    .with_system(show_level_summary)
             )
 
             .add_system_set(SystemSet::on_enter(AppState::Menu).with_system(on_enter));
@@ -23,8 +26,20 @@ impl Plugin for InMenuStatePlugin {
 #[derive(Component)]
 pub struct Cleanup;
 
+/// The game mode a menu button starts.
+#[derive(Component)]
+struct ModeButton(GameMode);
+
+/// Describes the level the Play button starts, from its info, once it has loaded.
+#[derive(Component)]
+struct LevelSummary;
+
+/// The level the Play button starts, loaded so its info can be shown.
+struct MenuLevel(Handle<LevelData>);
+
 fn on_enter(mut commands: Commands, asset_server: Res<AssetServer>) {
     let font = asset_server.load("font/roboto_thin.ttf");
+    commands.insert_resource(MenuLevel(asset_server.load(DEFAULT_LEVEL_PATH)));
 
     let style = TextStyle {
         font,
@@ -58,42 +73,74 @@ fn on_enter(mut commands: Commands, asset_server: Res<AssetServer>) {
                 ..Default::default()
             });
 
-            parent.spawn_bundle(ButtonBundle {
+            parent.spawn_bundle(NodeBundle {
                 style: Style {
-                    size: Size::new(Val::Auto, Val::Auto),
+                    flex_direction: FlexDirection::ColumnReverse,
                     margin: Rect { right: Val::Percent(20.0), ..Default::default() },
-                    padding: Rect { left: Val::Percent(1.0), right: Val::Percent(1.0), ..Default::default() },
-                    // horizontally center child text
-                    justify_content: JustifyContent::Center,
-                    // vertically center child text
-                    align_items: AlignItems::Center,
                     align_self: AlignSelf::Center,
                     ..Default::default()
                 },
-                color: UiColor(Color::rgb(102.0 / 255.0, 102.0 / 255.0, 102.0 / 255.0)),
+                color: Color::NONE.into(),
                 ..Default::default()
             }).with_children(|parent| {
-                parent.spawn_bundle(TextBundle {
-                    text: Text::with_section(
-                        "Play",
-                        style.clone(),
-                        TextAlignment::default(),
-                    ),
-                    ..Default::default()
-                });
+                for (label, mode) in [("Play", GameMode::Normal), ("Daily", GameMode::Daily)] {
+                    parent.spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Auto, Val::Auto),
+                            margin: Rect { bottom: Val::Px(20.0), ..Default::default() },
+                            padding: Rect { left: Val::Percent(1.0), right: Val::Percent(1.0), ..Default::default() },
+                            // horizontally center child text
+                            justify_content: JustifyContent::Center,
+                            // vertically center child text
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        color: UiColor(Color::rgb(102.0 / 255.0, 102.0 / 255.0, 102.0 / 255.0)),
+                        ..Default::default()
+                    })
+                    .insert(ModeButton(mode))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                label,
+                                style.clone(),
+                                TextAlignment::default(),
+                            ),
+                            ..Default::default()
+                        });
+                    });
+
+                    if mode == GameMode::Normal {
+                        parent.spawn_bundle(TextBundle {
+                            style: Style {
+                                margin: Rect { bottom: Val::Px(20.0), ..Default::default() },
+                                ..Default::default()
+                            },
+                            text: Text::with_section(
+                                "",
+                                TextStyle { font_size: 20.0, ..style.clone() },
+                                TextAlignment::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(LevelSummary);
+                    }
+                }
             });
         });
 }
 
 fn play_button(
-    mut interaction_query: Query<(&Interaction, &mut UiColor), With<Button>>,
+    mut interaction_query: Query<(&Interaction, &mut UiColor, &ModeButton), With<Button>>,
     mut state: ResMut<State<AppState>>,
+    mut game_mode: ResMut<GameMode>,
 ) {
-    for (interaction, mut color) in interaction_query.iter_mut() {
+    for (interaction, mut color, ModeButton(mode)) in interaction_query.iter_mut() {
         match interaction {
             Interaction::Clicked => {
                 *color = UiColor(Color::rgb(0.3, 0.5, 0.6));
 
+                *game_mode = *mode;
                 state.set(AppState::InGame).unwrap();
             },
             Interaction::Hovered => {
@@ -106,6 +153,23 @@ fn play_button(
     }
 }
 
+fn show_level_summary(
+    menu_level: Res<MenuLevel>,
+    levels: Res<Assets<LevelData>>,
+    mut summaries: Query<&mut Text, With<LevelSummary>>,
+) {
+    let summary = match levels.get(&menu_level.0).and_then(|level| level.info().summary()) {
+        Some(summary) => summary,
+        None => return,
+    };
+
+    for mut text in summaries.iter_mut() {
+        if text.sections[0].value != summary {
+            text.sections[0].value = summary.clone();
+        }
+    }
+}
+
 fn on_exit(
     mut commands: Commands,
     query: Query<Entity, With<Cleanup>>,
@@ -0,0 +1,3 @@
+pub mod app_state;
+pub mod in_game;
+pub mod in_menu;
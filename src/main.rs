@@ -1,6 +1,4 @@
-mod app_state;
-mod in_menu;
-mod in_game;
+use bevy_unfair_advantage::{app_state, in_game, in_menu};
 
 use app_state::*;
 